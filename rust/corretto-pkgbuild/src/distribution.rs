@@ -0,0 +1,272 @@
+//! Where releases of each distribution are published and how tarballs are found in them
+
+/// Distributions selectable with --distribution
+pub const DISTRIBUTIONS: &[Distribution] = &[
+    Distribution {
+        name: "corretto",
+        owner: "corretto",
+        repo: |jdk_version| format!("corretto-{}", jdk_version),
+        pkgver: str::to_owned,
+        directory: |_, file_name| file_name.strip_suffix(".tar.gz").map(str::to_owned),
+        extractor: Extractor::MarkdownTable,
+        template: include_str!("template/PKGBUILD"),
+        // Amazon Services LLC (Amazon Corretto release) <corretto-team@amazon.com>
+        validpgpkey: "6DC3636DAE534049C8B94623A122542AB04F24E3",
+    },
+    Distribution {
+        name: "temurin",
+        owner: "adoptium",
+        repo: |jdk_version| format!("temurin{}-binaries", jdk_version),
+        pkgver: temurin_pkgver,
+        directory: |tag_name, _| Some(temurin_directory(tag_name)),
+        extractor: Extractor::Assets {
+            names: &[
+                ("Linux x64", "-jdk_x64_linux_hotspot_"),
+                ("Linux aarch64", "-jdk_aarch64_linux_hotspot_"),
+                ("Linux arm", "-jdk_arm_linux_hotspot_"),
+                ("Alpine Linux x64", "-jdk_x64_alpine-linux_hotspot_"),
+                ("Alpine Linux aarch64", "-jdk_aarch64_alpine-linux_hotspot_"),
+            ],
+            checksum_suffix: ".sha256.txt",
+        },
+        template: include_str!("template/PKGBUILD.temurin"),
+        // Adoptium
+        validpgpkey: "3B04D753C9050D9A5D343F39843C48A565F8F04B",
+    },
+];
+
+/// jdk-17.0.8.1+1 -> 17.0.8.1_1 and jdk8u382-b05 -> 8u382_b05
+fn temurin_pkgver(tag_name: &str) -> String {
+    tag_name
+        .trim_start_matches("jdk-")
+        .trim_start_matches("jdk")
+        .replace(['+', '-'], "_")
+}
+
+/// Tarballs are extracted to the tag name regardless of their names, which is written with
+/// `$pkgver` so that --update doesn't have to rewrite it, e.g. jdk-${pkgver/_/+}
+fn temurin_directory(tag_name: &str) -> String {
+    let pkgver = temurin_pkgver(tag_name);
+    [("jdk-", "+"), ("jdk", "-")]
+        .into_iter()
+        .find(|(prefix, separator)| {
+            format!("{}{}", prefix, pkgver.replacen('_', separator, 1)) == tag_name
+        })
+        .map_or_else(
+            || tag_name.to_owned(),
+            |(prefix, separator)| format!("{}${{pkgver/_/{}}}", prefix, separator),
+        )
+}
+
+#[derive(Debug)]
+pub struct Distribution {
+    pub name: &'static str,
+    /// GitHub owner of the release repositories
+    pub owner: &'static str,
+    /// GitHub repository for the JDK version
+    pub repo: fn(u8) -> String,
+    /// pkgver from the tag name, which mustn't contain hyphens
+    pub pkgver: fn(&str) -> String,
+    /// Directory extracted from the tarball, from the tag name and the file name of the tarball. It
+    /// may refer to `$pkgver`
+    pub directory: fn(&str, &str) -> Option<String>,
+    pub extractor: Extractor,
+    /// Handlebars template of PKGBUILD
+    pub template: &'static str,
+    /// Fingerprint of the primary key which tarballs are signed with
+    pub validpgpkey: &'static str,
+}
+
+pub fn parse_distribution(s: &str) -> Result<&'static Distribution, String> {
+    DISTRIBUTIONS
+        .iter()
+        .find(|distribution| distribution.name == s)
+        .ok_or_else(|| {
+            format!(
+                "unknown distribution (possible values: {})",
+                DISTRIBUTIONS
+                    .iter()
+                    .map(|distribution| distribution.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// GitHub release with only the fields used to find tarballs
+#[derive(Debug, serde::Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub body: Option<String>,
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl From<octocrab::models::repos::Release> for Release {
+    fn from(release: octocrab::models::repos::Release) -> Self {
+        Self {
+            tag_name: release.tag_name,
+            body: release.body,
+            assets: release
+                .assets
+                .into_iter()
+                .map(|asset| Asset {
+                    name: asset.name,
+                    browser_download_url: asset.browser_download_url.into(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Extractor {
+    /// Table of platforms, downloads, checksums and signatures in the release notes
+    MarkdownTable,
+    /// Tarballs attached to the release with checksum files and signatures next to them, e.g.
+    /// `foo.tar.gz`, `foo.tar.gz.sha256.txt` and `foo.tar.gz.sig`
+    Assets {
+        /// Platforms and the part of the tarball names identifying them
+        names: &'static [(&'static str, &'static str)],
+        checksum_suffix: &'static str,
+    },
+}
+
+impl Extractor {
+    /// Tarballs with checksums and signatures by platform, e.g. "Linux x64"
+    pub async fn extract(
+        &self,
+        release: &Release,
+        client: &reqwest::Client,
+    ) -> anyhow::Result<std::collections::BTreeMap<String, crate::Tarball>> {
+        match self {
+            Self::MarkdownTable => match release.body {
+                Some(ref body) => crate::extract_tarballs(body),
+                None => Ok(Default::default()),
+            },
+            Self::Assets {
+                names,
+                checksum_suffix,
+            } => {
+                let find = |name: &str| {
+                    release
+                        .assets
+                        .iter()
+                        .find(|asset| asset.name == name)
+                        .map(|asset| &asset.browser_download_url)
+                };
+                let mut tarballs = std::collections::BTreeMap::new();
+                for (platform, pattern) in *names {
+                    let Some(asset) = release.assets.iter().find(|asset| {
+                        asset.name.contains(pattern) && asset.name.ends_with(".tar.gz")
+                    }) else {
+                        continue;
+                    };
+                    // Tarballs without checksums or signatures are skipped like in the release
+                    // notes
+                    let (Some(checksum), Some(signature)) = (
+                        find(&format!("{}{}", asset.name, checksum_suffix)),
+                        find(&format!("{}.sig", asset.name)),
+                    ) else {
+                        continue;
+                    };
+                    let checksum_file = client
+                        .get(checksum)
+                        .send()
+                        .await?
+                        .error_for_status()?
+                        .text()
+                        .await?;
+                    // <checksum>  <file name>
+                    let checksum_sha256 = checksum_file
+                        .split_ascii_whitespace()
+                        .next()
+                        .filter(|s| s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit()))
+                        .ok_or_else(|| anyhow::anyhow!("SHA-256 is not found in {}", checksum))?;
+                    tarballs.insert(
+                        (*platform).to_owned(),
+                        crate::Tarball {
+                            download: asset.browser_download_url.clone(),
+                            checksum_sha256: checksum_sha256.to_owned(),
+                            signature: signature.clone(),
+                        },
+                    );
+                }
+                Ok(tarballs)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn temurin_directory() {
+        assert_eq!(
+            super::temurin_directory("jdk-17.0.8.1+1"),
+            "jdk-${pkgver/_/+}"
+        );
+        assert_eq!(super::temurin_directory("jdk-21+35"), "jdk-${pkgver/_/+}");
+        assert_eq!(super::temurin_directory("jdk8u382-b05"), "jdk${pkgver/_/-}");
+        // Tag names which can't be derived from pkgver are written as is
+        assert_eq!(super::temurin_directory("jdk-17.0.8+7-1"), "jdk-17.0.8+7-1");
+    }
+
+    #[tokio::test]
+    async fn extract_assets() {
+        let addr = crate::test::file_server().await;
+        let release: super::Release = serde_json::from_str(
+            &include_str!("../test/temurin-17.0.8.1+1.json")
+                .replace("https://github.com", &format!("http://{}", addr)),
+        )
+        .unwrap();
+        let temurin = super::parse_distribution("temurin").unwrap();
+        let tarballs = temurin
+            .extractor
+            .extract(&release, &reqwest::Client::new())
+            .await
+            .unwrap();
+        // Alpine Linux x64 has no signature
+        assert_eq!(
+            tarballs.keys().collect::<Vec<_>>(),
+            vec!["Linux aarch64", "Linux x64"]
+        );
+        let download = format!(
+            "http://{}/adoptium/temurin17-binaries/releases/download/jdk-17.0.8.1%2B1/OpenJDK17U-jdk_x64_linux_hotspot_17.0.8.1_1.tar.gz",
+            addr
+        );
+        assert_eq!(
+            tarballs["Linux x64"],
+            crate::Tarball {
+                signature: format!("{}.sig", download),
+                download,
+                checksum_sha256: "c25dfbc334068a48c19c44ce39ad4b8427e309ae1cfa83f23c102e78b8a6dcc0"
+                    .to_owned(),
+            }
+        );
+        assert_eq!(
+            tarballs["Linux aarch64"].checksum_sha256,
+            "eefd3cf3b3dd47ed269fa7a5e9b7b1e6f4c1b6a7d1a0e0e6e6a04eb2d6a3f6a1"
+        );
+
+        use clap::Parser as _;
+        let args = crate::Args::parse_from(["corretto-pkgbuild", "-j", "17", "-d", "temurin"]);
+        let pkgbuild = crate::Pkgbuild::new(args, &release.tag_name, &tarballs).unwrap();
+        assert_eq!(pkgbuild.pkgver, "17.0.8.1_1");
+        let rendered = crate::new_handlebars()
+            .unwrap()
+            .render("temurin", &pkgbuild)
+            .unwrap();
+        assert!(rendered.contains(
+            "\n      cp -a \"jdk-${pkgver/_/+}\" \"$pkgdir/usr/lib/jvm/java-17-temurin\"\n"
+        ));
+        assert!(
+            rendered.contains("/OpenJDK17U-jdk_x64_linux_hotspot_${pkgver}.tar.gz\"{,.sig})\n")
+        );
+    }
+}
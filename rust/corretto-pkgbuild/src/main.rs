@@ -1,22 +1,89 @@
+mod distribution;
+mod srcinfo;
+mod update;
+mod verify;
+
 #[derive(Debug, clap::Parser, serde::Serialize)]
 struct Args {
     /// JDK version
     #[clap(short, long)]
     jdk_version: u8,
-    /// pkgrel
-    #[clap(short, long, default_value_t = 1)]
-    pkgrel: u8,
+    /// Distribution whose GitHub releases are packaged
+    #[clap(short, long, value_parser = distribution::parse_distribution, default_value = "corretto")]
+    #[serde(skip)]
+    distribution: &'static distribution::Distribution,
+    /// pkgrel [default: 1, or unchanged with --update when pkgver isn't changed]
+    #[clap(short, long)]
+    #[serde(skip)]
+    pkgrel: Option<u8>,
     /// Maintainer header name
     #[clap(short, long)]
     maintainer: Option<String>,
+    /// Platforms to package (e.g. "Alpine Linux x64,Alpine Linux aarch64" for musl)
+    #[clap(
+        long = "platform",
+        value_parser = parse_platform,
+        value_delimiter = ',',
+        default_value = "Linux x64,Linux aarch64"
+    )]
+    #[serde(skip)]
+    platforms: Vec<String>,
+    /// Download the tarballs and verify them against the published checksums and signatures
+    #[clap(long, requires = "gpg_key")]
+    #[serde(skip)]
+    verify: bool,
+    /// Public key to verify signatures of the tarballs with, required by --verify. The key must be
+    /// the one in validpgpkeys
+    #[clap(long, value_name = "FILE", requires = "verify")]
+    #[serde(skip)]
+    gpg_key: Option<std::path::PathBuf>,
+    /// Update pkgver, pkgrel, sources and checksums of the existing PKGBUILD in place instead of
+    /// printing a new one, and write .SRCINFO next to it
+    #[clap(long, value_name = "PKGBUILD")]
+    #[serde(skip)]
+    update: Option<std::path::PathBuf>,
+}
+
+/// Platforms and their architecture names in Arch Linux. Names are the ones in the release notes
+/// of Amazon Corretto
+const PLATFORMS: &[(&str, &str)] = &[
+    ("Linux x64", "x86_64"),
+    ("Linux aarch64", "aarch64"),
+    ("Linux x86", "i686"),
+    ("Linux arm", "armv7h"),
+    ("Alpine Linux x64", "x86_64"),
+    ("Alpine Linux aarch64", "aarch64"),
+    ("Linux arm musl", "armv7h"),
+];
+
+fn parse_platform(s: &str) -> Result<String, String> {
+    if PLATFORMS.iter().any(|(platform, _)| *platform == s) {
+        Ok(s.to_owned())
+    } else {
+        Err(format!(
+            "unknown platform (possible values: {})",
+            PLATFORMS
+                .iter()
+                .map(|(platform, _)| *platform)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+}
+
+fn carch(platform: &str) -> &'static str {
+    PLATFORMS
+        .iter()
+        .find_map(|(p, carch)| (*p == platform).then_some(*carch))
+        .expect("platforms are validated by parse_platform")
 }
 
-#[derive(Debug, PartialEq, serde::Serialize)]
-struct Tarballs {
-    x86_64_download: String,
-    x86_64_checksum_sha256: String,
-    aarch64_download: String,
-    aarch64_checksum_sha256: String,
+/// Tarball with its published checksum and signature
+#[derive(Debug, PartialEq)]
+struct Tarball {
+    download: String,
+    checksum_sha256: String,
+    signature: String,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -24,8 +91,88 @@ struct Pkgbuild {
     #[serde(flatten)]
     args: Args,
     pkgver: String,
-    #[serde(flatten)]
-    tarballs: Tarballs,
+    pkgrel: u8,
+    sources: Vec<Source>,
+    validpgpkey: &'static str,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Source {
+    carch: &'static str,
+    /// Download URL with pkgver replaced with `${pkgver}`
+    source: String,
+    /// Directory extracted from the tarball
+    directory: String,
+    checksum_sha256: String,
+    /// Spaces to align the second element of sha256sums
+    checksum_indent: String,
+}
+
+impl Pkgbuild {
+    /// Fails when any of `args.platforms` is missing in `tarballs`
+    fn new(
+        args: Args,
+        tag_name: &str,
+        tarballs: &std::collections::BTreeMap<String, Tarball>,
+    ) -> anyhow::Result<Self> {
+        let pkgver = (args.distribution.pkgver)(tag_name);
+        let missing: Vec<_> = args
+            .platforms
+            .iter()
+            .filter(|platform| !tarballs.contains_key(*platform))
+            .map(String::as_str)
+            .collect();
+        anyhow::ensure!(
+            missing.is_empty(),
+            "Required platforms are not found in {}: {} (found: {})",
+            pkgver,
+            missing.join(", "),
+            tarballs
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        let mut sources = Vec::with_capacity(args.platforms.len());
+        for platform in &args.platforms {
+            let tarball = &tarballs[platform];
+            let file_name = tarball
+                .download
+                .rsplit('/')
+                .next()
+                .unwrap_or(&tarball.download);
+            let directory =
+                (args.distribution.directory)(tag_name, file_name).ok_or_else(|| {
+                    anyhow::anyhow!("{} is not a tarball: {}", platform, tarball.download)
+                })?;
+            let carch = carch(platform);
+            sources.push(Source {
+                carch,
+                source: tarball.download.replace(&pkgver, "${pkgver}"),
+                directory: directory.replace(&pkgver, "$pkgver"),
+                checksum_sha256: tarball.checksum_sha256.clone(),
+                checksum_indent: " ".repeat("sha256sums_=(".len() + carch.len()),
+            });
+        }
+        Ok(Self {
+            pkgrel: args.pkgrel.unwrap_or(1),
+            validpgpkey: args.distribution.validpgpkey,
+            args,
+            pkgver,
+            sources,
+        })
+    }
+}
+
+fn new_handlebars() -> anyhow::Result<handlebars::Handlebars<'static>> {
+    let mut handlebars = handlebars::Handlebars::new();
+    handlebars.set_strict_mode(true);
+    handlebars.register_escape_fn(handlebars::no_escape);
+    for distribution in distribution::DISTRIBUTIONS {
+        handlebars.register_template_string(distribution.name, distribution.template)?;
+    }
+    Ok(handlebars)
 }
 
 #[tokio::main]
@@ -40,15 +187,24 @@ async fn main() -> anyhow::Result<()> {
 
     use clap::Parser as _;
     let args = Args::parse();
+    for (i, platform) in args.platforms.iter().enumerate() {
+        if let Some(other) = args.platforms[..i]
+            .iter()
+            .find(|other| carch(other) == carch(platform))
+        {
+            anyhow::bail!("{} and {} are both {}", other, platform, carch(platform));
+        }
+    }
 
-    let mut handlebars = handlebars::Handlebars::new();
-    handlebars.set_strict_mode(true);
-    handlebars.register_escape_fn(handlebars::no_escape);
-    handlebars.register_template_string("PKGBUILD", include_str!("template/PKGBUILD"))?;
+    let handlebars = new_handlebars()?;
 
+    let client = reqwest::Client::new();
     let octocrab = octocrab::instance();
     let mut page = octocrab
-        .repos("corretto", format!("corretto-{}", args.jdk_version))
+        .repos(
+            args.distribution.owner,
+            (args.distribution.repo)(args.jdk_version),
+        )
         .releases()
         .list()
         .per_page(1)
@@ -66,13 +222,45 @@ async fn main() -> anyhow::Result<()> {
             tracing::info!("Skip draft release: {}", release.html_url);
         } else if release.prerelease {
             tracing::info!("Skip pre-release: {}", release.html_url);
-        } else if let Some(body) = release.body {
-            if let Some(tarballs) = extract_tarballs(&body)? {
-                break Pkgbuild {
-                    args,
-                    pkgver: release.tag_name,
-                    tarballs,
+        } else {
+            let release = distribution::Release::from(release);
+            let tarballs = args
+                .distribution
+                .extractor
+                .extract(&release, &client)
+                .await?;
+            tracing::info!(
+                "Found platforms in {}: {}",
+                release.tag_name,
+                tarballs
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            // Releases only for other platforms, e.g. macOS, are skipped
+            if args.platforms.iter().any(|p| tarballs.contains_key(p)) {
+                let verifier = if args.verify {
+                    Some(
+                        verify::Verifier::new(
+                            args.gpg_key
+                                .as_deref()
+                                .expect("--verify requires --gpg-key"),
+                            args.distribution.validpgpkey,
+                        )
+                        .await?,
+                    )
+                } else {
+                    None
                 };
+                let pkgbuild = Pkgbuild::new(args, &release.tag_name, &tarballs)?;
+                if let Some(verifier) = verifier {
+                    for platform in &pkgbuild.args.platforms {
+                        verifier.verify(&tarballs[platform]).await?;
+                        tracing::info!("Verified {}", tarballs[platform].download);
+                    }
+                }
+                break pkgbuild;
             }
         }
 
@@ -82,8 +270,17 @@ async fn main() -> anyhow::Result<()> {
             .ok_or_else(|| anyhow::anyhow!("No more releases"))?;
     };
 
-    let stdout = std::io::stdout().lock();
-    handlebars.render_to_write("PKGBUILD", &pkgbuild, stdout)?;
+    if let Some(ref path) = pkgbuild.args.update {
+        let current = std::fs::read_to_string(path)?;
+        let updated = update::update(&current, &pkgbuild, pkgbuild.args.pkgrel)?;
+        let srcinfo = srcinfo::generate(&updated)?;
+        std::fs::write(path, updated)?;
+        std::fs::write(path.with_file_name(".SRCINFO"), srcinfo)?;
+        tracing::info!("Updated {} to {}", path.display(), pkgbuild.pkgver);
+    } else {
+        let stdout = std::io::stdout().lock();
+        handlebars.render_to_write(pkgbuild.args.distribution.name, &pkgbuild, stdout)?;
+    }
 
     Ok(())
 }
@@ -125,7 +322,8 @@ where
     Ok(false)
 }
 
-fn extract_tarballs(body: &str) -> anyhow::Result<Option<Tarballs>> {
+/// Tarballs with signatures by platform, e.g. "Linux x64"
+fn extract_tarballs(body: &str) -> anyhow::Result<std::collections::BTreeMap<String, Tarball>> {
     let mut parser = pulldown_cmark::Parser::new_ext(body, pulldown_cmark::Options::ENABLE_TABLES);
     anyhow::ensure!(
         skip_until_tag(&mut parser, |tag| {
@@ -146,14 +344,7 @@ fn extract_tarballs(body: &str) -> anyhow::Result<Option<Tarballs>> {
         }
     });
 
-    #[derive(Debug)]
-    struct Entry {
-        download: String,
-        checksum_sha256: String,
-    }
-
-    let mut x86_64_entry = None;
-    let mut aarch64_entry = None;
+    let mut tarballs = std::collections::BTreeMap::new();
     for row in rows {
         let mut cells = row.into_iter().filter_map(|n| {
             if let Node::Element(Element {
@@ -184,49 +375,40 @@ fn extract_tarballs(body: &str) -> anyhow::Result<Option<Tarballs>> {
             .next()
             .ok_or_else(|| anyhow::anyhow!("Sig File cell is not found"))?;
         if !sigfile.is_empty() {
-            let download = download
-                .into_iter()
-                .find_map(|n| {
-                    if let Node::Element(Element {
-                        tag: pulldown_cmark::Tag::Link { dest_url, .. },
-                        ..
-                    }) = n
-                    {
-                        Some(dest_url)
-                    } else {
-                        None
-                    }
-                })
+            let download = find_link(download)
                 .ok_or_else(|| anyhow::anyhow!("Link element is not found in Download cell"))?;
+            let signature = find_link(sigfile)
+                .ok_or_else(|| anyhow::anyhow!("Link element is not found in Sig File cell"))?;
             let checksum_sha256 = checksum
                 .into_iter()
                 .rev()
                 .find_map(|n| if let Node::Code(s) = n { Some(s) } else { None })
                 .ok_or_else(|| anyhow::anyhow!("Code node is not found in Checksum cell"))?;
-            if &*platform == "Linux x64" {
-                x86_64_entry = Some(Entry {
-                    download: download.into_string(),
-                    checksum_sha256: checksum_sha256.into_string(),
-                });
-            } else if &*platform == "Linux aarch64" {
-                aarch64_entry = Some(Entry {
+            tarballs.insert(
+                platform.trim().to_owned(),
+                Tarball {
                     download: download.into_string(),
                     checksum_sha256: checksum_sha256.into_string(),
-                });
-            }
+                    signature: signature.into_string(),
+                },
+            );
         }
     }
+    Ok(tarballs)
+}
 
-    if let (Some(x86_64_entry), Some(aarch64_entry)) = (x86_64_entry, aarch64_entry) {
-        Ok(Some(Tarballs {
-            x86_64_download: x86_64_entry.download,
-            x86_64_checksum_sha256: x86_64_entry.checksum_sha256,
-            aarch64_download: aarch64_entry.download,
-            aarch64_checksum_sha256: aarch64_entry.checksum_sha256,
-        }))
-    } else {
-        Ok(None)
-    }
+fn find_link(nodes: Vec<Node>) -> Option<pulldown_cmark::CowStr> {
+    nodes.into_iter().find_map(|n| {
+        if let Node::Element(Element {
+            tag: pulldown_cmark::Tag::Link { dest_url, .. },
+            ..
+        }) = n
+        {
+            Some(dest_url)
+        } else {
+            None
+        }
+    })
 }
 
 fn textify(nodes: Vec<Node>) -> tendril::StrTendril {
@@ -274,42 +456,171 @@ fn build_nodes<'a>(parser: &mut pulldown_cmark::Parser<'a>) -> Vec<Node<'a>> {
 
 #[cfg(test)]
 mod test {
+    /// Serve files under test/ over HTTP by the last segment of the path
+    pub(crate) async fn file_server() -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let len = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..len]);
+                let path = request
+                    .split(' ')
+                    .nth(1)
+                    .unwrap()
+                    .rsplit('/')
+                    .next()
+                    .unwrap();
+                let response = match std::fs::read(
+                    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                        .join("test")
+                        .join(path),
+                ) {
+                    Ok(body) => {
+                        let mut response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            body.len()
+                        )
+                        .into_bytes();
+                        response.extend(body);
+                        response
+                    }
+                    Err(_) => {
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_vec()
+                    }
+                };
+                stream.write_all(&response).await.unwrap();
+            }
+        });
+        addr
+    }
+
     #[test]
     fn extract_tarballs_from_release() {
         let body = include_str!("../test/corretto-8.342.07.4.md");
-        let tarballs = super::extract_tarballs(body).unwrap().unwrap();
-        assert_eq!(tarballs.x86_64_download, "https://corretto.aws/downloads/resources/8.342.07.4/amazon-corretto-8.342.07.4-linux-x64.tar.gz");
+        let tarballs = super::extract_tarballs(body).unwrap();
+        assert_eq!(
+            tarballs["Linux x64"].download,
+            "https://corretto.aws/downloads/resources/8.342.07.4/amazon-corretto-8.342.07.4-linux-x64.tar.gz"
+        );
         assert_eq!(
-            tarballs.x86_64_checksum_sha256,
+            tarballs["Linux x64"].checksum_sha256,
             "f10fc46f42df58cf26a4689a7016aa610b691ad4e8be7c349f8651dec79d4e41"
         );
-        assert_eq!(tarballs.aarch64_download, "https://corretto.aws/downloads/resources/8.342.07.4/amazon-corretto-8.342.07.4-linux-aarch64.tar.gz");
         assert_eq!(
-            tarballs.aarch64_checksum_sha256,
+            tarballs["Linux aarch64"].download,
+            "https://corretto.aws/downloads/resources/8.342.07.4/amazon-corretto-8.342.07.4-linux-aarch64.tar.gz"
+        );
+        assert_eq!(
+            tarballs["Linux aarch64"].checksum_sha256,
             "2d454c4804fc2ee5a2aef9f517ca6c2b85dee7728d74edf20f85a35681b2d143"
         );
+        assert_eq!(
+            tarballs["Linux aarch64"].signature,
+            "https://corretto.aws/downloads/resources/8.342.07.4/amazon-corretto-8.342.07.4-linux-aarch64.tar.gz.sig"
+        );
+    }
+
+    #[test]
+    fn verify_requires_gpg_key() {
+        use clap::Parser as _;
+
+        let e =
+            super::Args::try_parse_from(["corretto-pkgbuild", "-j", "17", "--verify"]).unwrap_err();
+        assert_eq!(e.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        let args = super::Args::try_parse_from([
+            "corretto-pkgbuild",
+            "-j",
+            "17",
+            "--verify",
+            "--gpg-key",
+            "corretto.key",
+        ])
+        .unwrap();
+        assert!(args.verify);
     }
 
     #[test]
-    fn return_none_if_no_linux_platforms() {
+    fn no_linux_platforms() {
         let body = include_str!("../test/corretto-11.0.16.8.3.md");
         let tarballs = super::extract_tarballs(body).unwrap();
-        assert_eq!(tarballs, None);
+        assert_eq!(
+            tarballs.keys().collect::<Vec<_>>(),
+            vec!["macOS aarch64", "macOS x64"]
+        );
     }
 
     #[test]
     fn table_without_leading_empty_line() {
         let body = include_str!("../test/corretto-11.0.20.9.1.md");
-        let tarballs = super::extract_tarballs(body).unwrap().unwrap();
-        assert_eq!(tarballs.x86_64_download, "https://corretto.aws/downloads/resources/11.0.20.9.1/amazon-corretto-11.0.20.9.1-linux-x64.tar.gz");
+        let tarballs = super::extract_tarballs(body).unwrap();
+        assert_eq!(
+            tarballs["Linux x64"].download,
+            "https://corretto.aws/downloads/resources/11.0.20.9.1/amazon-corretto-11.0.20.9.1-linux-x64.tar.gz"
+        );
         assert_eq!(
-            tarballs.x86_64_checksum_sha256,
+            tarballs["Linux x64"].checksum_sha256,
             "b6150255d304eab8fdcc0422beab277e5395bc481b4f87f096da78a979e47d47"
         );
-        assert_eq!(tarballs.aarch64_download, "https://corretto.aws/downloads/resources/11.0.20.9.1/amazon-corretto-11.0.20.9.1-linux-aarch64.tar.gz");
         assert_eq!(
-            tarballs.aarch64_checksum_sha256,
+            tarballs["Linux aarch64"].download,
+            "https://corretto.aws/downloads/resources/11.0.20.9.1/amazon-corretto-11.0.20.9.1-linux-aarch64.tar.gz"
+        );
+        assert_eq!(
+            tarballs["Linux aarch64"].checksum_sha256,
             "17c33bd5fb51fd8b4b5cdfce9d656f31698a6c6ccf018f4f2bf99d714948c736"
         );
+        assert_eq!(
+            tarballs["Alpine Linux x64"].download,
+            "https://corretto.aws/downloads/resources/11.0.20.9.1/amazon-corretto-11.0.20.9.1-alpine-linux-x64.tar.gz"
+        );
+    }
+
+    #[test]
+    fn render_pkgbuild() {
+        use clap::Parser as _;
+
+        let tarballs =
+            super::extract_tarballs(include_str!("../test/corretto-8.342.07.4.md")).unwrap();
+        let args = super::Args::parse_from(["corretto-pkgbuild", "-j", "8"]);
+        let pkgbuild = super::Pkgbuild::new(args, "8.342.07.4", &tarballs).unwrap();
+        let rendered = super::new_handlebars()
+            .unwrap()
+            .render("corretto", &pkgbuild)
+            .unwrap();
+        let readme = include_str!("../README.md");
+        let expected = readme
+            .split_once("% corretto-pkgbuild -j 8\n")
+            .unwrap()
+            .1
+            .split_once("```")
+            .unwrap()
+            .0;
+        // The maintainer line is empty
+        assert_eq!(rendered.trim_start(), expected);
+        assert_eq!(
+            super::srcinfo::generate(&rendered).unwrap(),
+            include_str!("../test/corretto-8.342.07.4.SRCINFO")
+        );
+
+        let tarballs =
+            super::extract_tarballs(include_str!("../test/corretto-8.342.07.4.md")).unwrap();
+        let args = super::Args::parse_from([
+            "corretto-pkgbuild",
+            "-j",
+            "8",
+            "--platform",
+            "Alpine Linux x64,Alpine Linux aarch64",
+        ]);
+        let e = super::Pkgbuild::new(args, "8.342.07.4", &tarballs).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Required platforms are not found in 8.342.07.4: Alpine Linux aarch64 (found: Alpine Linux x64, Linux aarch64, Linux x64)"
+        );
     }
 }
@@ -0,0 +1,374 @@
+//! Generate .SRCINFO without makepkg. Only top-level assignments are evaluated, and values may
+//! contain quotes, variables assigned earlier (`$pkgver`, `${pkgver}`) and brace expansion (`{,.sig}`)
+
+const SINGLE_VALUED: &[&str] = &[
+    "pkgdesc",
+    "pkgver",
+    "pkgrel",
+    "epoch",
+    "url",
+    "install",
+    "changelog",
+];
+
+const CHECKSUMS: &[&str] = &[
+    "cksums",
+    "md5sums",
+    "sha1sums",
+    "sha224sums",
+    "sha256sums",
+    "sha384sums",
+    "sha512sums",
+    "b2sums",
+];
+
+const MULTI_VALUED: &[&str] = &[
+    "arch",
+    "groups",
+    "license",
+    "checkdepends",
+    "makedepends",
+    "depends",
+    "optdepends",
+    "provides",
+    "conflicts",
+    "replaces",
+    "noextract",
+    "options",
+    "backup",
+    "source",
+    "validpgpkeys",
+];
+
+const MULTI_VALUED_ARCH: &[&str] = &[
+    "source",
+    "provides",
+    "conflicts",
+    "depends",
+    "replaces",
+    "optdepends",
+    "makedepends",
+    "checkdepends",
+];
+
+/// Attributes are written in the same order as `makepkg --printsrcinfo`
+pub fn generate(pkgbuild: &str) -> anyhow::Result<String> {
+    let variables = parse(pkgbuild)?;
+    let get = |name: &str| variables.get(name).map(Vec::as_slice).unwrap_or_default();
+    let pkgnames = get("pkgname");
+    let pkgbase = get("pkgbase")
+        .first()
+        .or_else(|| pkgnames.first())
+        .ok_or_else(|| anyhow::anyhow!("pkgname is not found in PKGBUILD"))?;
+
+    let mut srcinfo = format!("pkgbase = {}\n", pkgbase);
+    let mut write = |name: &str| {
+        for value in get(name) {
+            if !value.is_empty() {
+                srcinfo.push_str(&format!("\t{} = {}\n", name, value));
+            }
+        }
+    };
+    for name in SINGLE_VALUED.iter().chain(MULTI_VALUED).chain(CHECKSUMS) {
+        write(name);
+    }
+    for arch in get("arch") {
+        // There are no architecture-specific attributes for any
+        if arch == "any" {
+            continue;
+        }
+        for name in MULTI_VALUED_ARCH.iter().chain(CHECKSUMS) {
+            write(&format!("{}_{}", name, arch));
+        }
+    }
+    srcinfo.push('\n');
+    for pkgname in pkgnames {
+        srcinfo.push_str(&format!("pkgname = {}\n\n", pkgname));
+    }
+    Ok(srcinfo)
+}
+
+/// Values of top-level assignments. Function bodies and other commands are skipped
+pub fn parse(pkgbuild: &str) -> anyhow::Result<std::collections::BTreeMap<String, Vec<String>>> {
+    let mut variables = std::collections::BTreeMap::new();
+    let mut lines = pkgbuild.lines();
+    // Array values can continue to following lines
+    let mut pending = String::new();
+    while let Some(line) = lines.next() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.contains("()") && line.trim_end().ends_with('{') {
+            // Skip the function until the closing brace at the beginning of a line
+            for line in lines.by_ref() {
+                if line.starts_with('}') {
+                    break;
+                }
+            }
+            continue;
+        }
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (name, append) = match name.strip_suffix('+') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            continue;
+        }
+
+        pending.clear();
+        pending.push_str(value);
+        let values = if value.starts_with('(') {
+            while !is_closed(&pending) {
+                let line = lines
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Array {} is not closed", name))?;
+                pending.push('\n');
+                pending.push_str(line);
+            }
+            parse_words(&pending[1..], &variables)?
+        } else {
+            parse_words(&pending, &variables)?
+                .into_iter()
+                .take(1)
+                .collect()
+        };
+        let entry = variables.entry(name.to_owned()).or_insert_with(Vec::new);
+        if !append {
+            entry.clear();
+        }
+        entry.extend(values);
+    }
+    Ok(variables)
+}
+
+/// Whether the array starting with `(` has its closing parenthesis
+fn is_closed(s: &str) -> bool {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut comment = false;
+    for c in s.chars() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (_, '\n') if comment => comment = false,
+            (_, _) if comment => {}
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '#') => comment = true,
+            (None, ')') => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Words until `)`, `;` or a comment. A word can expand to multiple words with braces
+fn parse_words(
+    s: &str,
+    variables: &std::collections::BTreeMap<String, Vec<String>>,
+) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek() {
+            None | Some(')') | Some(';') => break,
+            Some('#') => while chars.next_if(|c| *c != '\n').is_some() {},
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, ')' | ';'))
+                {
+                    match c {
+                        '\'' => {
+                            word.push(c);
+                            for c in chars.by_ref() {
+                                word.push(c);
+                                if c == '\'' {
+                                    break;
+                                }
+                            }
+                        }
+                        '"' => {
+                            word.push(c);
+                            let mut escaped = false;
+                            for c in chars.by_ref() {
+                                word.push(c);
+                                if escaped {
+                                    escaped = false;
+                                } else if c == '\\' {
+                                    escaped = true;
+                                } else if c == '"' {
+                                    break;
+                                }
+                            }
+                        }
+                        '\\' => {
+                            word.push(c);
+                            word.extend(chars.next());
+                        }
+                        _ => word.push(c),
+                    }
+                }
+                words.extend(
+                    expand_braces(&word)
+                        .iter()
+                        .map(|w| expand(w, variables))
+                        .collect::<anyhow::Result<Vec<_>>>()?,
+                );
+            }
+        }
+    }
+    Ok(words)
+}
+
+/// Expand the first unquoted `{a,b}` and the rest recursively
+fn expand_braces(word: &str) -> Vec<String> {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut open = None;
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    for (i, c) in word.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            // ${var} is not a brace expansion
+            (None, '{') if i > 0 && word.as_bytes()[i - 1] == b'$' => depth += 1,
+            (None, '{') => {
+                if open.is_none() {
+                    open = Some(i);
+                    depth = 0;
+                } else {
+                    depth += 1;
+                }
+            }
+            (None, ',') if open.is_some() && depth == 0 => commas.push(i),
+            (None, '}') if depth > 0 => depth -= 1,
+            (None, '}') if open.is_some() => {
+                let start = open.unwrap();
+                if commas.is_empty() {
+                    // Not an expansion, e.g. {} or {a}
+                    open = None;
+                    continue;
+                }
+                let (prefix, suffix) = (&word[..start], &word[i + 1..]);
+                let mut bounds = vec![start];
+                bounds.extend(&commas);
+                bounds.push(i);
+                let mut words = Vec::new();
+                for alternative in bounds.windows(2) {
+                    let alternative = &word[alternative[0] + 1..alternative[1]];
+                    words.extend(expand_braces(&format!(
+                        "{}{}{}",
+                        prefix, alternative, suffix
+                    )));
+                }
+                return words;
+            }
+            _ => {}
+        }
+    }
+    vec![word.to_owned()]
+}
+
+/// Remove quotes and expand variables
+fn expand(
+    word: &str,
+    variables: &std::collections::BTreeMap<String, Vec<String>>,
+) -> anyhow::Result<String> {
+    let mut expanded = String::new();
+    let mut chars = word.chars().peekable();
+    let mut in_double_quotes = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' if !in_double_quotes => {
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    expanded.push(c);
+                }
+            }
+            '"' => in_double_quotes = !in_double_quotes,
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    if in_double_quotes && !matches!(c, '"' | '\\' | '$' | '`') {
+                        expanded.push('\\');
+                    }
+                    expanded.push(c);
+                }
+            }
+            '$' => {
+                let name: String = if chars.next_if_eq(&'{').is_some() {
+                    let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    anyhow::ensure!(
+                        name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                        "Unsupported parameter expansion: ${{{}}}",
+                        name
+                    );
+                    name
+                } else if chars.peek() == Some(&'(') {
+                    anyhow::bail!("Command substitution is not supported: {}", word);
+                } else {
+                    std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_'))
+                        .collect()
+                };
+                if name.is_empty() {
+                    expanded.push('$');
+                } else if let Some(value) = variables.get(&name).and_then(|v| v.first()) {
+                    expanded.push_str(value);
+                }
+            }
+            _ => expanded.push(c),
+        }
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse() {
+        let variables = super::parse(
+            r#"pkgname=foo
+pkgver=1.2.3
+url="https://example.com/$pkgname"
+source=("https://example.com/${pkgver}/foo.tar.gz"{,.sig} # comment
+        'bar-$pkgver.patch')
+depends=(a)
+depends+=("b c")
+package() {
+  pkgver=4.5.6
+}
+"#,
+        )
+        .unwrap();
+        assert_eq!(variables["url"], vec!["https://example.com/foo"]);
+        assert_eq!(
+            variables["source"],
+            vec![
+                "https://example.com/1.2.3/foo.tar.gz",
+                "https://example.com/1.2.3/foo.tar.gz.sig",
+                "bar-$pkgver.patch",
+            ]
+        );
+        assert_eq!(variables["depends"], vec!["a", "b c"]);
+        assert_eq!(variables["pkgver"], vec!["1.2.3"]);
+    }
+}
@@ -0,0 +1,176 @@
+/// Update pkgver, pkgrel, sources and checksums of the existing PKGBUILD and leave the rest as is.
+/// pkgrel is kept when pkgver isn't changed unless given explicitly
+pub fn update(pkgbuild: &str, new: &crate::Pkgbuild, pkgrel: Option<u8>) -> anyhow::Result<String> {
+    let variables = crate::srcinfo::parse(pkgbuild)?;
+    let current = |name: &str| {
+        variables
+            .get(name)
+            .and_then(|values| values.first())
+            .ok_or_else(|| anyhow::anyhow!("{} is not found in PKGBUILD", name))
+    };
+    let pkgrel = match pkgrel {
+        Some(pkgrel) => pkgrel.to_string(),
+        None if current("pkgver")? == &new.pkgver => current("pkgrel")?.clone(),
+        None => "1".to_owned(),
+    };
+
+    let mut pkgbuild = pkgbuild.to_owned();
+    replace_assignment(&mut pkgbuild, "pkgver", &new.pkgver)?;
+    replace_assignment(&mut pkgbuild, "pkgrel", &pkgrel)?;
+    for source in &new.sources {
+        replace_assignment(
+            &mut pkgbuild,
+            &format!("source_{}", source.carch),
+            &format!("(\"{}\"{{,.sig}})", source.source),
+        )?;
+        replace_assignment(
+            &mut pkgbuild,
+            &format!("sha256sums_{}", source.carch),
+            &format!(
+                "('{}'\n{}'SKIP')",
+                source.checksum_sha256, source.checksum_indent
+            ),
+        )?;
+    }
+    Ok(pkgbuild)
+}
+
+/// Replace the value of the top-level assignment `name=...`
+fn replace_assignment(pkgbuild: &mut String, name: &str, value: &str) -> anyhow::Result<()> {
+    let prefix = format!("{}=", name);
+    let start = std::iter::once(0)
+        .chain(pkgbuild.match_indices('\n').map(|(i, _)| i + 1))
+        .find(|i| pkgbuild[*i..].starts_with(&prefix))
+        .ok_or_else(|| anyhow::anyhow!("{} is not found in PKGBUILD", name))?
+        + prefix.len();
+    let end = start + value_len(&pkgbuild[start..])?;
+    pkgbuild.replace_range(start..end, value);
+    Ok(())
+}
+
+/// Length of the assigned value, which is an array or a word
+fn value_len(s: &str) -> anyhow::Result<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i + 1);
+                }
+            }
+            (None, c) if depth == 0 && (c.is_whitespace() || c == ';') => return Ok(i),
+            _ => {}
+        }
+    }
+    anyhow::ensure!(depth == 0 && quote.is_none(), "Unterminated value: {}", s);
+    Ok(s.len())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn update() {
+        use clap::Parser as _;
+
+        let tarballs =
+            crate::extract_tarballs(include_str!("../test/corretto-11.0.20.9.1.md")).unwrap();
+        let args = crate::Args::parse_from(["corretto-pkgbuild", "-j", "11"]);
+        let new = crate::Pkgbuild::new(args, "11.0.20.9.1", &tarballs).unwrap();
+        let pkgbuild = r#"# Maintainer: someone
+pkgname=amazon-corretto-11
+pkgver=11.0.16.8.3
+pkgrel=2 # rebuilt
+arch=('x86_64' 'aarch64')
+optdepends=('alsa-lib: for sound')
+source_x86_64=("https://corretto.aws/downloads/resources/${pkgver}/amazon-corretto-${pkgver}-linux-x64.tar.gz"{,.sig})
+source_aarch64=("https://corretto.aws/downloads/resources/${pkgver}/amazon-corretto-${pkgver}-linux-aarch64.tar.gz"{,.sig})
+
+package() {
+  echo customized
+}
+
+sha256sums_x86_64=('0000000000000000000000000000000000000000000000000000000000000000'
+                   'SKIP')
+sha256sums_aarch64=('1111111111111111111111111111111111111111111111111111111111111111' 'SKIP')
+"#;
+        assert_eq!(
+            super::update(pkgbuild, &new, None).unwrap(),
+            r#"# Maintainer: someone
+pkgname=amazon-corretto-11
+pkgver=11.0.20.9.1
+pkgrel=1 # rebuilt
+arch=('x86_64' 'aarch64')
+optdepends=('alsa-lib: for sound')
+source_x86_64=("https://corretto.aws/downloads/resources/${pkgver}/amazon-corretto-${pkgver}-linux-x64.tar.gz"{,.sig})
+source_aarch64=("https://corretto.aws/downloads/resources/${pkgver}/amazon-corretto-${pkgver}-linux-aarch64.tar.gz"{,.sig})
+
+package() {
+  echo customized
+}
+
+sha256sums_x86_64=('b6150255d304eab8fdcc0422beab277e5395bc481b4f87f096da78a979e47d47'
+                   'SKIP')
+sha256sums_aarch64=('17c33bd5fb51fd8b4b5cdfce9d656f31698a6c6ccf018f4f2bf99d714948c736'
+                    'SKIP')
+"#
+        );
+
+        let updated = super::update(pkgbuild, &new, None).unwrap();
+        // pkgrel is kept when the version is the same
+        assert!(
+            super::update(&updated.replace("pkgrel=1", "pkgrel=3"), &new, None)
+                .unwrap()
+                .contains("\npkgrel=3 # rebuilt\n")
+        );
+
+        // The directory of Temurin follows pkgver
+        let temurin = |tag_name: &str, checksum_sha256: &str| {
+            let args = crate::Args::parse_from([
+                "corretto-pkgbuild",
+                "-j",
+                "17",
+                "-d",
+                "temurin",
+                "--platform",
+                "Linux x64",
+            ]);
+            let download = format!(
+                "https://github.com/adoptium/temurin17-binaries/releases/download/{}/OpenJDK17U-jdk_x64_linux_hotspot_{}.tar.gz",
+                tag_name.replace('+', "%2B"),
+                (args.distribution.pkgver)(tag_name)
+            );
+            let tarballs = [(
+                "Linux x64".to_owned(),
+                crate::Tarball {
+                    signature: format!("{}.sig", download),
+                    download,
+                    checksum_sha256: checksum_sha256.to_owned(),
+                },
+            )]
+            .into();
+            crate::Pkgbuild::new(args, tag_name, &tarballs).unwrap()
+        };
+        let handlebars = crate::new_handlebars().unwrap();
+        let old = handlebars
+            .render("temurin", &temurin("jdk-17.0.7+7", &"0".repeat(64)))
+            .unwrap();
+        let new = temurin("jdk-17.0.8.1+1", &"1".repeat(64));
+        assert_eq!(
+            super::update(&old, &new, None).unwrap(),
+            handlebars.render("temurin", &new).unwrap()
+        );
+    }
+}
@@ -0,0 +1,144 @@
+/// Download tarballs and verify them against the checksums and signatures in the release notes
+pub struct Verifier {
+    client: reqwest::Client,
+    /// GNUPGHOME with the imported public key
+    gnupg_home: tempfile::TempDir,
+    /// Fingerprint of the primary key which signatures must be made with
+    fingerprint: String,
+}
+
+impl Verifier {
+    pub async fn new(gpg_key: &std::path::Path, fingerprint: &str) -> anyhow::Result<Self> {
+        let gnupg_home = tempfile::Builder::new()
+            .prefix("corretto-pkgbuild-")
+            .tempdir()?;
+        let output = tokio::process::Command::new("gpg")
+            .arg("--homedir")
+            .arg(gnupg_home.path())
+            .arg("--batch")
+            .arg("--import")
+            .arg(gpg_key)
+            .output()
+            .await?;
+        anyhow::ensure!(
+            output.status.success(),
+            "Failed to import {}: {}",
+            gpg_key.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Ok(Self {
+            client: reqwest::Client::new(),
+            gnupg_home,
+            fingerprint: fingerprint.to_owned(),
+        })
+    }
+
+    pub async fn verify(&self, tarball: &crate::Tarball) -> anyhow::Result<()> {
+        use sha2::Digest as _;
+        use std::io::Write as _;
+
+        let mut file = tempfile::NamedTempFile::new()?;
+        let mut hasher = sha2::Sha256::new();
+        let mut response = self
+            .client
+            .get(&tarball.download)
+            .send()
+            .await?
+            .error_for_status()?;
+        while let Some(chunk) = response.chunk().await? {
+            hasher.update(&chunk);
+            file.write_all(&chunk)?;
+        }
+        file.flush()?;
+        let checksum_sha256 = format!("{:x}", hasher.finalize());
+        anyhow::ensure!(
+            checksum_sha256.eq_ignore_ascii_case(&tarball.checksum_sha256),
+            "SHA-256 of {} is {} but {} is published",
+            tarball.download,
+            checksum_sha256,
+            tarball.checksum_sha256
+        );
+
+        let signature = self
+            .client
+            .get(&tarball.signature)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let mut signature_file = tempfile::NamedTempFile::new()?;
+        signature_file.write_all(&signature)?;
+        signature_file.flush()?;
+        let output = tokio::process::Command::new("gpg")
+            .arg("--homedir")
+            .arg(self.gnupg_home.path())
+            .arg("--batch")
+            .arg("--status-fd=1")
+            .arg("--verify")
+            .arg(signature_file.path())
+            .arg(file.path())
+            .output()
+            .await?;
+        anyhow::ensure!(
+            output.status.success(),
+            "Failed to verify {}: {}",
+            tarball.signature,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        // VALIDSIG <fingerprint> ... <primary key fingerprint>
+        let status = String::from_utf8_lossy(&output.stdout);
+        let signed = status.lines().any(|line| {
+            line.strip_prefix("[GNUPG:] VALIDSIG ").is_some_and(|args| {
+                let args: Vec<_> = args.split_ascii_whitespace().collect();
+                args.first() == Some(&self.fingerprint.as_str())
+                    || args.last() == Some(&self.fingerprint.as_str())
+            })
+        });
+        anyhow::ensure!(
+            signed,
+            "{} is not signed by {}",
+            tarball.signature,
+            self.fingerprint
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    const FINGERPRINT: &str = "3685CE90C97F58E6451D96456BC516AD48A1E61D";
+    const CHECKSUM: &str = "9e18f85e2a60fd10030e329297ae69d2b7bf15c8c8f125ea13ddd2ac45adbbfa";
+
+    fn tarball(addr: std::net::SocketAddr, checksum_sha256: &str) -> crate::Tarball {
+        let download = format!("http://{}/amazon-corretto-8.0.0.1-linux-x64.tar.gz", addr);
+        crate::Tarball {
+            signature: format!("{}.sig", download),
+            download,
+            checksum_sha256: checksum_sha256.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_checksum_and_signature() {
+        let addr = crate::test::file_server().await;
+        let key = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test/test-key.asc");
+        let verifier = super::Verifier::new(&key, FINGERPRINT).await.unwrap();
+        verifier.verify(&tarball(addr, CHECKSUM)).await.unwrap();
+
+        let e = verifier
+            .verify(&tarball(addr, &"0".repeat(64)))
+            .await
+            .unwrap_err();
+        assert!(e.to_string().starts_with("SHA-256 of "), "{}", e);
+
+        let verifier = super::Verifier::new(&key, &"0".repeat(40)).await.unwrap();
+        let e = verifier.verify(&tarball(addr, CHECKSUM)).await.unwrap_err();
+        assert!(
+            e.to_string()
+                .ends_with(" is not signed by 0000000000000000000000000000000000000000"),
+            "{}",
+            e
+        );
+    }
+}
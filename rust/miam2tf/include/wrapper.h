@@ -12,3 +12,18 @@ mrb_int wrapper_mrb_nil_p(mrb_value o);
 struct RClass* wrapper_e_runtime_error(mrb_state *mrb);
 mrb_value wrapper_mrb_nil_value(void);
 mrb_int wrapper_mrb_integer(mrb_value n);
+mrb_value wrapper_mrb_obj_value(struct RObject *p);
+mrb_int wrapper_mrb_string_p(mrb_value o);
+mrb_int wrapper_mrb_array_p(mrb_value o);
+mrb_int wrapper_mrb_integer_p(mrb_value o);
+mrb_value wrapper_mrb_bool_value(mrb_bool b);
+mrb_value wrapper_mrb_int_value(mrb_state *mrb, mrb_int i);
+mrb_value wrapper_mrb_float_value(mrb_state *mrb, mrb_float f);
+mrb_int wrapper_mrb_test(mrb_value o);
+mrb_int wrapper_mrb_hash_p(mrb_value o);
+mrb_int wrapper_mrb_true_p(mrb_value o);
+mrb_int wrapper_mrb_false_p(mrb_value o);
+const char* wrapper_rstring_ptr(mrb_value s);
+mrb_int wrapper_rstring_len(mrb_value s);
+int wrapper_mrb_gc_arena_save(mrb_state *mrb);
+void wrapper_mrb_gc_arena_restore(mrb_state *mrb, int idx);
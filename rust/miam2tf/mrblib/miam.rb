@@ -1,4 +1,4 @@
-Root = Struct.new(:users, :groups, :roles, :managed_policies, :instance_profiles) do
+Root = Struct.new(:users, :groups, :roles, :managed_policies, :instance_profiles, :excludes) do
   def initialize
     super
     self.users ||= []
@@ -6,6 +6,7 @@ Root = Struct.new(:users, :groups, :roles, :managed_policies, :instance_profiles
     self.roles ||= []
     self.managed_policies ||= []
     self.instance_profiles ||= []
+    self.excludes ||= []
   end
 end
 
@@ -16,7 +17,7 @@ def user(name, path: nil, &block)
   user = User.new
   user.user_name = name
   user.path = path
-  UserContext.new(user, context).instance_eval(&block)
+  UserContext.new(user, context.merge(user_name: name)).instance_eval(&block)
   @root.users << user
 end
 
@@ -24,7 +25,7 @@ def group(name, path: nil, &block)
   group = Group.new
   group.name = name
   group.path = path
-  GroupContext.new(group, context).instance_eval(&block)
+  GroupContext.new(group, context.merge(group_name: name)).instance_eval(&block)
   @root.groups << group
 end
 
@@ -32,15 +33,17 @@ def role(name, path: nil, &block)
   role = Role.new
   role.name = name
   role.path = path
-  RoleContext.new(role, context).instance_eval(&block)
+  RoleContext.new(role, context.merge(role_name: name)).instance_eval(&block)
   @root.roles << role
 end
 
-def managed_policy(name, path: nil, &block)
+def managed_policy(name, path: nil, description: nil, tags: {}, &block)
   policy = ManagedPolicy.new
   policy.name = name
   policy.path = path
-  raw = ManagedPolicyContext.new.instance_eval(&block)
+  policy.description = description
+  policy.tags = Tag.from_hash(tags)
+  raw = ManagedPolicyContext.new(context.merge(policy_name: name)).instance_eval(&block)
   policy.policy_document = PolicyDocument.from_raw('ManagedPolicy', raw)
   @root.managed_policies << policy
 end
@@ -50,23 +53,40 @@ def context
 end
 
 def template(name, &block)
-  @context.templates[name] = block
+  @context.templates[name.to_s] = block
 end
 
 def instance_profile(name, path: nil)
   @root.instance_profiles << InstanceProfile.new(name, path)
 end
 
-def exclude(_pattern)
-  # miam2tf doesn't need to handle `exclude` method
+def exclude(pattern)
+  unless pattern.is_a?(Regexp)
+    raise TypeError, "exclude expects Regexp but got #{pattern.class}"
+  end
+  # The source is converted to the syntax of regex crate by the loader
+  source = pattern.source
+  flags = ''
+  flags << 'i' if (pattern.options & Regexp::IGNORECASE) != 0
+  flags << 's' if (pattern.options & Regexp::MULTILINE) != 0
+  flags << 'x' if (pattern.options & Regexp::EXTENDED) != 0
+  source = "(?#{flags})#{source}" unless flags.empty?
+  @root.excludes << source
 end
 
-User = Struct.new(:user_name, :path, :policies, :groups, :attached_managed_policies) do
+User = Struct.new(:user_name, :path, :policies, :groups, :attached_managed_policies, :permissions_boundary, :tags) do
   def initialize
     super
     self.policies ||= []
     self.groups ||= []
     self.attached_managed_policies ||= []
+    self.tags ||= []
+  end
+end
+
+Tag = Struct.new(:key, :value) do
+  def self.from_hash(hash)
+    hash.map { |key, value| new(key.to_s, value.to_s) }
   end
 end
 
@@ -76,11 +96,17 @@ module TemplateHelper
   end
 
   def include_template(template_name, context = {})
-    block = @context.templates[template_name]
+    block = @context.templates[template_name.to_s]
+    unless block
+      raise ArgumentError, "Template `#{template_name}` is not defined"
+    end
     saved = @context
     @context = @context.merge(context)
-    instance_eval(&block)
-    @context = saved
+    begin
+      instance_eval(&block)
+    ensure
+      @context = saved
+    end
     nil
   end
 end
@@ -104,6 +130,14 @@ class UserContext
   def attached_managed_policies(*policies)
     @user.attached_managed_policies.concat(policies.map(&:to_s))
   end
+
+  def permissions_boundary(policy_arn)
+    @user.permissions_boundary = policy_arn.to_s
+  end
+
+  def tags(hash)
+    @user.tags.concat(Tag.from_hash(hash))
+  end
 end
 
 def json_principals_to_array(raw)
@@ -193,12 +227,14 @@ class GroupContext
   end
 end
 
-Role = Struct.new(:name, :path, :assume_role_policy_document, :policies, :attached_managed_policies, :instance_profiles, :max_session_duration) do
+Role = Struct.new(:name, :path, :assume_role_policy_document, :policies, :attached_managed_policies, :instance_profiles, :max_session_duration, :permissions_boundary, :tags, :description, :force_detach_policies, :custom_suffix) do
   def initialize
     super
     self.policies ||= []
     self.instance_profiles ||= []
     self.attached_managed_policies ||= []
+    self.tags ||= []
+    self.force_detach_policies ||= false
   end
 end
 
@@ -229,11 +265,36 @@ class RoleContext
   def max_session_duration(duration)
     @role.max_session_duration = duration.to_i
   end
+
+  def permissions_boundary(policy_arn)
+    @role.permissions_boundary = policy_arn.to_s
+  end
+
+  def tags(hash)
+    @role.tags.concat(Tag.from_hash(hash))
+  end
+
+  def description(description)
+    @role.description = description.to_s
+  end
+
+  def force_detach_policies(enabled = true)
+    @role.force_detach_policies = enabled ? true : false
+  end
+
+  def custom_suffix(suffix)
+    @role.custom_suffix = suffix.to_s
+  end
 end
 
-ManagedPolicy = Struct.new(:name, :path, :policy_document)
+ManagedPolicy = Struct.new(:name, :path, :policy_document, :description, :tags)
 
 class ManagedPolicyContext
+  include TemplateHelper
+
+  def initialize(context)
+    @context = context
+  end
 end
 
 InstanceProfile = Struct.new(:name, :path)
@@ -0,0 +1,377 @@
+use crate::validate::Problem;
+
+const MIN_SESSION_DURATION: i64 = 3600;
+const MAX_SESSION_DURATION: i64 = 43200;
+
+/// Report problems found in the model, including ones reported by [`crate::validate::validate`]
+pub fn check(miam: &crate::Miam) -> Result<(), crate::validate::Error> {
+    let mut problems = crate::validate::problems(miam);
+
+    check_duplicates(
+        &mut problems,
+        "user",
+        miam.users.iter().map(|u| u.user_name.as_str()),
+    );
+    check_duplicates(
+        &mut problems,
+        "group",
+        miam.groups.iter().map(|g| g.name.as_str()),
+    );
+    check_duplicates(
+        &mut problems,
+        "role",
+        miam.roles.iter().map(|r| r.name.as_str()),
+    );
+    check_duplicates(
+        &mut problems,
+        "managed_policy",
+        miam.managed_policies.iter().map(|p| p.name.as_str()),
+    );
+    check_duplicates(
+        &mut problems,
+        "instance_profile",
+        miam.instance_profiles.iter().map(|p| p.name.as_str()),
+    );
+
+    let group_names: std::collections::HashSet<_> =
+        miam.groups.iter().map(|g| g.name.as_str()).collect();
+    let instance_profile_names: std::collections::HashSet<_> = miam
+        .instance_profiles
+        .iter()
+        .map(|p| p.name.as_str())
+        .collect();
+
+    for user in &miam.users {
+        let location = format!("user({})", user.user_name);
+        for group in &user.groups {
+            if !group_names.contains(group.as_str()) && !miam.is_excluded(group) {
+                problems.push(Problem {
+                    location: location.clone(),
+                    message: format!("group {:?} is not defined", group),
+                });
+            }
+        }
+        check_policy_documents(&mut problems, &location, &user.policies);
+        check_managed_policy_attachments(
+            &mut problems,
+            &location,
+            &user.attached_managed_policies,
+            miam,
+        );
+    }
+
+    for group in &miam.groups {
+        let location = format!("group({})", group.name);
+        check_policy_documents(&mut problems, &location, &group.policies);
+        check_managed_policy_attachments(
+            &mut problems,
+            &location,
+            &group.attached_managed_policies,
+            miam,
+        );
+    }
+
+    for role in &miam.roles {
+        let location = format!("role({})", role.name);
+        for profile in &role.instance_profiles {
+            if !instance_profile_names.contains(profile.as_str()) {
+                problems.push(Problem {
+                    location: location.clone(),
+                    message: format!("instance profile {:?} is not defined", profile),
+                });
+            }
+        }
+        if let Some(ref policy) = role.assume_role_policy_document {
+            check_effects(&mut problems, &location, policy);
+        }
+        check_policy_documents(&mut problems, &location, &role.policies);
+        check_managed_policy_attachments(
+            &mut problems,
+            &location,
+            &role.attached_managed_policies,
+            miam,
+        );
+        if role.service_linked_service_name().is_some()
+            && !(role.policies.is_empty() && role.attached_managed_policies.is_empty())
+        {
+            problems.push(Problem {
+                location: location.clone(),
+                message: "policies of service-linked role are managed by AWS and ignored"
+                    .to_owned(),
+            });
+        }
+        if let Some(ref suffix) = role.custom_suffix {
+            if role.service_linked_service_name().is_none() {
+                problems.push(Problem {
+                    location: location.clone(),
+                    message: "custom_suffix is only used by service-linked roles".to_owned(),
+                });
+            } else if !role.name.ends_with(&format!("_{}", suffix)) {
+                problems.push(Problem {
+                    location: location.clone(),
+                    message: format!("role name must end with \"_{}\" of custom_suffix", suffix),
+                });
+            }
+        }
+        if let Some(duration) = role.max_session_duration {
+            if !(MIN_SESSION_DURATION..=MAX_SESSION_DURATION).contains(&duration) {
+                problems.push(Problem {
+                    location: location.clone(),
+                    message: format!(
+                        "max_session_duration {} is out of range ({}-{})",
+                        duration, MIN_SESSION_DURATION, MAX_SESSION_DURATION
+                    ),
+                });
+            }
+        }
+    }
+
+    for policy in &miam.managed_policies {
+        let location = format!("managed_policy({})", policy.name);
+        check_effects(&mut problems, &location, &policy.policy_document);
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(crate::validate::Error { problems })
+    }
+}
+
+fn check_duplicates<'a, I>(problems: &mut Vec<Problem>, kind: &str, names: I)
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut seen = std::collections::HashSet::new();
+    let mut reported = std::collections::HashSet::new();
+    for name in names {
+        if !seen.insert(name) && reported.insert(name) {
+            problems.push(Problem {
+                location: format!("{}({})", kind, name),
+                message: format!("{} {:?} is defined more than once", kind, name),
+            });
+        }
+    }
+}
+
+fn check_policy_documents(
+    problems: &mut Vec<Problem>,
+    location: &str,
+    policies: &[crate::PolicyDocument],
+) {
+    let mut seen = std::collections::HashSet::new();
+    for policy in policies {
+        if !seen.insert(policy.name.as_str()) {
+            problems.push(Problem {
+                location: location.to_owned(),
+                message: format!("policy {:?} is defined more than once", policy.name),
+            });
+        }
+        check_effects(problems, location, policy);
+    }
+}
+
+fn check_effects(problems: &mut Vec<Problem>, location: &str, policy: &crate::PolicyDocument) {
+    for (i, statement) in policy.statements.iter().enumerate() {
+        if statement.effect != "Allow" && statement.effect != "Deny" {
+            problems.push(Problem {
+                location: format!("{}.policy({}).statements[{}]", location, policy.name, i),
+                message: format!(
+                    "Effect must be \"Allow\" or \"Deny\" but got {:?}",
+                    statement.effect
+                ),
+            });
+        }
+    }
+}
+
+fn check_managed_policy_attachments(
+    problems: &mut Vec<Problem>,
+    location: &str,
+    policy_arns: &[String],
+    miam: &crate::Miam,
+) {
+    for policy_arn in policy_arns {
+        if policy_arn.starts_with("arn:aws:iam::aws:policy/") {
+            continue;
+        }
+        let Some(short_policy_name) = crate::printer::short_policy_name(policy_arn) else {
+            // Already reported by validate
+            continue;
+        };
+        if miam.is_excluded(short_policy_name) {
+            continue;
+        }
+        let defined = miam.find_managed_policy(policy_arn).is_some();
+        if !defined {
+            problems.push(Problem {
+                location: location.to_owned(),
+                message: format!(
+                    "attached managed policy {:?} is not defined in managed_policies",
+                    policy_arn
+                ),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{group, managed_policy, miam, policy, role, statement, strings, user};
+
+    fn problems(miam: &crate::Miam) -> Vec<String> {
+        match super::check(miam) {
+            Ok(()) => Vec::new(),
+            Err(e) => e.problems.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut miam = miam();
+        miam.users
+            .extend([user("alice"), user("alice"), user("alice")]);
+        miam.groups.extend([group("admin"), group("admin")]);
+        miam.roles.extend([role("web"), role("web")]);
+        miam.managed_policies.extend([
+            managed_policy("deny", Vec::new()),
+            managed_policy("deny", Vec::new()),
+        ]);
+        miam.instance_profiles.extend([
+            crate::InstanceProfile {
+                name: "web".to_owned(),
+                path: None,
+            },
+            crate::InstanceProfile {
+                name: "web".to_owned(),
+                path: None,
+            },
+        ]);
+        assert_eq!(
+            problems(&miam),
+            vec![
+                r#"user(alice): user "alice" is defined more than once"#,
+                r#"group(admin): group "admin" is defined more than once"#,
+                r#"role(web): role "web" is defined more than once"#,
+                r#"managed_policy(deny): managed_policy "deny" is defined more than once"#,
+                r#"instance_profile(web): instance_profile "web" is defined more than once"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn undefined_references() {
+        let mut miam = miam();
+        miam.excludes.push(regex::Regex::new("^legacy-").unwrap());
+        miam.users.push(crate::User {
+            groups: strings(&["admin", "developer", "legacy-admin"]),
+            attached_managed_policies: strings(&[
+                "arn:aws:iam::aws:policy/ReadOnlyAccess",
+                "arn:aws:iam::123456789012:policy/deny",
+                "arn:aws:iam::123456789012:policy/legacy-deny",
+                "arn:aws:iam::123456789012:policy/ops/undefined",
+            ]),
+            ..user("alice")
+        });
+        miam.groups.push(group("admin"));
+        miam.roles.push(crate::Role {
+            instance_profiles: strings(&["web"]),
+            attached_managed_policies: strings(&["arn:aws:iam::123456789012:policy/deny"]),
+            ..role("web")
+        });
+        miam.managed_policies.push(crate::ManagedPolicy {
+            // The path doesn't match the ARN
+            path: Some("/ops/".to_owned()),
+            ..managed_policy("deny", Vec::new())
+        });
+        assert_eq!(
+            problems(&miam),
+            vec![
+                r#"user(alice): group "developer" is not defined"#,
+                r#"user(alice): attached managed policy "arn:aws:iam::123456789012:policy/deny" is not defined in managed_policies"#,
+                r#"user(alice): attached managed policy "arn:aws:iam::123456789012:policy/ops/undefined" is not defined in managed_policies"#,
+                r#"role(web): instance profile "web" is not defined"#,
+                r#"role(web): attached managed policy "arn:aws:iam::123456789012:policy/deny" is not defined in managed_policies"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn policies() {
+        let mut miam = miam();
+        miam.users.push(crate::User {
+            policies: vec![policy("s3", Vec::new()), policy("s3", Vec::new())],
+            ..user("alice")
+        });
+        miam.groups.push(crate::Group {
+            policies: vec![policy("s3", vec![statement("allow", &["s3:*"], &["*"])])],
+            ..group("admin")
+        });
+        miam.roles.push(crate::Role {
+            assume_role_policy_document: Some(policy(
+                "assume",
+                vec![
+                    statement("Allow", &["sts:AssumeRole"], &[]),
+                    statement("Permit", &["sts:AssumeRole"], &[]),
+                ],
+            )),
+            ..role("web")
+        });
+        miam.managed_policies.push(managed_policy(
+            "deny",
+            vec![statement("DENY", &["*"], &["*"])],
+        ));
+        assert_eq!(
+            problems(&miam),
+            vec![
+                r#"user(alice): policy "s3" is defined more than once"#,
+                r#"group(admin).policy(s3).statements[0]: Effect must be "Allow" or "Deny" but got "allow""#,
+                r#"role(web).policy(assume).statements[1]: Effect must be "Allow" or "Deny" but got "Permit""#,
+                r#"managed_policy(deny).policy(deny).statements[0]: Effect must be "Allow" or "Deny" but got "DENY""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn roles() {
+        let mut miam = miam();
+        miam.roles.push(crate::Role {
+            path: Some("/aws-service-role/elasticbeanstalk.amazonaws.com/".to_owned()),
+            attached_managed_policies: strings(&["arn:aws:iam::aws:policy/ReadOnlyAccess"]),
+            ..role("AWSServiceRoleForElasticBeanstalk")
+        });
+        miam.roles.push(crate::Role {
+            max_session_duration: Some(60),
+            ..role("short")
+        });
+        miam.roles.push(crate::Role {
+            max_session_duration: Some(43200),
+            custom_suffix: Some("long".to_owned()),
+            ..role("long")
+        });
+        miam.roles.push(crate::Role {
+            path: Some("/aws-service-role/autoscaling.amazonaws.com/".to_owned()),
+            custom_suffix: Some("web".to_owned()),
+            ..role("AWSServiceRoleForAutoScaling_batch")
+        });
+        assert_eq!(
+            problems(&miam),
+            vec![
+                "role(AWSServiceRoleForElasticBeanstalk): policies of service-linked role are managed by AWS and ignored",
+                "role(short): max_session_duration 60 is out of range (3600-43200)",
+                "role(long): custom_suffix is only used by service-linked roles",
+                r#"role(AWSServiceRoleForAutoScaling_batch): role name must end with "_web" of custom_suffix"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn includes_validate_problems() {
+        let mut miam = miam();
+        miam.users.push(user("alice bob"));
+        assert_eq!(
+            problems(&miam),
+            vec![r#"user(alice bob): "alice bob" cannot be used as Terraform resource name"#]
+        );
+    }
+}
@@ -0,0 +1,499 @@
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Modified {
+        location: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added(location) => write!(f, "+ {}", location),
+            Self::Removed(location) => write!(f, "- {}", location),
+            Self::Modified { location, old, new } => {
+                write!(f, "~ {}: {} -> {}", location, old, new)
+            }
+        }
+    }
+}
+
+/// Report semantic changes between two models. Locations are formatted like the ones of
+/// [`crate::check::check`]
+pub fn diff(old: &crate::Miam, new: &crate::Miam) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_named(
+        &mut changes,
+        "user",
+        &old.users,
+        &new.users,
+        |u| &u.user_name,
+        |changes, location, old, new| {
+            diff_value(changes, &format!("{}.path", location), &old.path, &new.path);
+            diff_set(changes, location, "groups", &old.groups, &new.groups);
+            diff_policies(changes, location, &old.policies, &new.policies);
+            diff_set(
+                changes,
+                location,
+                "attached_managed_policies",
+                &old.attached_managed_policies,
+                &new.attached_managed_policies,
+            );
+            diff_value(
+                changes,
+                &format!("{}.permissions_boundary", location),
+                &old.permissions_boundary,
+                &new.permissions_boundary,
+            );
+            diff_tags(changes, location, &old.tags, &new.tags);
+        },
+    );
+    diff_named(
+        &mut changes,
+        "group",
+        &old.groups,
+        &new.groups,
+        |g| &g.name,
+        |changes, location, old, new| {
+            diff_value(changes, &format!("{}.path", location), &old.path, &new.path);
+            diff_policies(changes, location, &old.policies, &new.policies);
+            diff_set(
+                changes,
+                location,
+                "attached_managed_policies",
+                &old.attached_managed_policies,
+                &new.attached_managed_policies,
+            );
+        },
+    );
+    diff_named(
+        &mut changes,
+        "role",
+        &old.roles,
+        &new.roles,
+        |r| &r.name,
+        |changes, location, old, new| {
+            diff_value(changes, &format!("{}.path", location), &old.path, &new.path);
+            let location_assume = format!("{}.assume_role_policy_document", location);
+            match (
+                &old.assume_role_policy_document,
+                &new.assume_role_policy_document,
+            ) {
+                (Some(old), Some(new)) => diff_policy_document(changes, &location_assume, old, new),
+                (None, Some(_)) => changes.push(Change::Added(location_assume)),
+                (Some(_), None) => changes.push(Change::Removed(location_assume)),
+                (None, None) => {}
+            }
+            diff_policies(changes, location, &old.policies, &new.policies);
+            diff_set(
+                changes,
+                location,
+                "attached_managed_policies",
+                &old.attached_managed_policies,
+                &new.attached_managed_policies,
+            );
+            diff_set(
+                changes,
+                location,
+                "instance_profiles",
+                &old.instance_profiles,
+                &new.instance_profiles,
+            );
+            diff_value(
+                changes,
+                &format!("{}.max_session_duration", location),
+                &old.max_session_duration,
+                &new.max_session_duration,
+            );
+            diff_value(
+                changes,
+                &format!("{}.permissions_boundary", location),
+                &old.permissions_boundary,
+                &new.permissions_boundary,
+            );
+            diff_value(
+                changes,
+                &format!("{}.description", location),
+                &old.description,
+                &new.description,
+            );
+            diff_value(
+                changes,
+                &format!("{}.force_detach_policies", location),
+                &old.force_detach_policies,
+                &new.force_detach_policies,
+            );
+            diff_tags(changes, location, &old.tags, &new.tags);
+        },
+    );
+    diff_named(
+        &mut changes,
+        "managed_policy",
+        &old.managed_policies,
+        &new.managed_policies,
+        |p| &p.name,
+        |changes, location, old, new| {
+            diff_value(changes, &format!("{}.path", location), &old.path, &new.path);
+            diff_value(
+                changes,
+                &format!("{}.description", location),
+                &old.description,
+                &new.description,
+            );
+            diff_policy_document(
+                changes,
+                &format!("{}.policy_document", location),
+                &old.policy_document,
+                &new.policy_document,
+            );
+            diff_tags(changes, location, &old.tags, &new.tags);
+        },
+    );
+    diff_named(
+        &mut changes,
+        "instance_profile",
+        &old.instance_profiles,
+        &new.instance_profiles,
+        |p| &p.name,
+        |changes, location, old, new| {
+            diff_value(changes, &format!("{}.path", location), &old.path, &new.path);
+        },
+    );
+    changes
+}
+
+/// Match entries by name and report added and removed ones in the order of names
+fn diff_named<'a, T, N, F>(
+    changes: &mut Vec<Change>,
+    kind: &str,
+    old: &'a [T],
+    new: &'a [T],
+    name: N,
+    mut diff_entry: F,
+) where
+    N: Fn(&'a T) -> &'a str,
+    F: FnMut(&mut Vec<Change>, &str, &'a T, &'a T),
+{
+    let old: std::collections::BTreeMap<_, _> = old.iter().map(|x| (name(x), x)).collect();
+    let new: std::collections::BTreeMap<_, _> = new.iter().map(|x| (name(x), x)).collect();
+    let mut names: Vec<_> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let location = format!("{}({})", kind, name);
+        match (old.get(name), new.get(name)) {
+            (Some(old), Some(new)) => diff_entry(changes, &location, old, new),
+            (None, Some(_)) => changes.push(Change::Added(location)),
+            (Some(_), None) => changes.push(Change::Removed(location)),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+fn diff_value<T>(changes: &mut Vec<Change>, location: &str, old: &T, new: &T)
+where
+    T: std::fmt::Debug + PartialEq,
+{
+    if old != new {
+        changes.push(Change::Modified {
+            location: location.to_owned(),
+            old: format!("{:?}", old),
+            new: format!("{:?}", new),
+        });
+    }
+}
+
+/// Order of group names, policy ARNs and so on doesn't matter
+fn diff_set(
+    changes: &mut Vec<Change>,
+    location: &str,
+    field: &str,
+    old: &[String],
+    new: &[String],
+) {
+    let old: std::collections::BTreeSet<_> = old.iter().collect();
+    let new: std::collections::BTreeSet<_> = new.iter().collect();
+    for name in old.difference(&new) {
+        changes.push(Change::Removed(format!("{}.{}({})", location, field, name)));
+    }
+    for name in new.difference(&old) {
+        changes.push(Change::Added(format!("{}.{}({})", location, field, name)));
+    }
+}
+
+fn diff_tags(
+    changes: &mut Vec<Change>,
+    location: &str,
+    old: &std::collections::BTreeMap<String, String>,
+    new: &std::collections::BTreeMap<String, String>,
+) {
+    let mut keys: Vec<_> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let location = format!("{}.tags({})", location, key);
+        match (old.get(key), new.get(key)) {
+            (Some(old), Some(new)) => diff_value(changes, &location, old, new),
+            (None, Some(_)) => changes.push(Change::Added(location)),
+            (Some(_), None) => changes.push(Change::Removed(location)),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+fn diff_policies(
+    changes: &mut Vec<Change>,
+    location: &str,
+    old: &[crate::PolicyDocument],
+    new: &[crate::PolicyDocument],
+) {
+    diff_named(
+        changes,
+        &format!("{}.policy", location),
+        old,
+        new,
+        |p| &p.name,
+        diff_policy_document,
+    );
+}
+
+/// Statements are matched by their sids, then by their contents regardless of their positions.
+/// The rest are compared in order. Statements without sids are located by their positions in
+/// the old document if removed and in the new document otherwise.
+fn diff_policy_document(
+    changes: &mut Vec<Change>,
+    location: &str,
+    old: &crate::PolicyDocument,
+    new: &crate::PolicyDocument,
+) {
+    diff_value(
+        changes,
+        &format!("{}.version", location),
+        &old.version,
+        &new.version,
+    );
+
+    let statement_location = |i: usize, statement: &crate::PolicyStatement| match statement.sid {
+        Some(ref sid) => format!("{}.statements({})", location, sid),
+        None => format!("{}.statements[{}]", location, i),
+    };
+    let mut old_rest: Vec<_> = old.statements.iter().enumerate().collect();
+    let mut new_rest = Vec::new();
+    let mut pairs = Vec::new();
+    for (i, statement) in new.statements.iter().enumerate() {
+        let found = old_rest.iter().position(|(_, old)| {
+            if statement.sid.is_some() {
+                old.sid == statement.sid
+            } else {
+                same_statement(old, statement)
+            }
+        });
+        match found {
+            Some(j) => pairs.push((old_rest.remove(j).1, i, statement)),
+            None => new_rest.push((i, statement)),
+        }
+    }
+    // Pair the rest in order so that small modifications are reported field by field
+    let (old_rest, mut removed): (Vec<_>, Vec<_>) =
+        old_rest.into_iter().partition(|(_, old)| old.sid.is_none());
+    let mut old_rest = old_rest.into_iter();
+    for (i, statement) in new_rest {
+        if statement.sid.is_none() {
+            if let Some((_, old)) = old_rest.next() {
+                pairs.push((old, i, statement));
+                continue;
+            }
+        }
+        changes.push(Change::Added(statement_location(i, statement)));
+    }
+    removed.extend(old_rest);
+    removed.sort_by_key(|(i, _)| *i);
+    for (i, statement) in removed {
+        changes.push(Change::Removed(statement_location(i, statement)));
+    }
+    pairs.sort_by_key(|(_, i, _)| *i);
+    for (old, i, new) in pairs {
+        diff_statement(changes, &statement_location(i, new), old, new);
+    }
+}
+
+/// Actions and resources are compared as sets
+fn same_statement(old: &crate::PolicyStatement, new: &crate::PolicyStatement) -> bool {
+    fn set(v: &[String]) -> std::collections::BTreeSet<&String> {
+        v.iter().collect()
+    }
+    old.sid == new.sid
+        && old.effect == new.effect
+        && set(&old.actions) == set(&new.actions)
+        && set(&old.not_actions) == set(&new.not_actions)
+        && set(&old.resources) == set(&new.resources)
+        && set(&old.not_resources) == set(&new.not_resources)
+        && old.conditions == new.conditions
+        && old.principals == new.principals
+        && old.not_principals == new.not_principals
+}
+
+fn diff_statement(
+    changes: &mut Vec<Change>,
+    location: &str,
+    old: &crate::PolicyStatement,
+    new: &crate::PolicyStatement,
+) {
+    let field = |name: &str| format!("{}.{}", location, name);
+    diff_value(changes, &field("sid"), &old.sid, &new.sid);
+    diff_value(changes, &field("effect"), &old.effect, &new.effect);
+    diff_set(changes, location, "actions", &old.actions, &new.actions);
+    diff_set(
+        changes,
+        location,
+        "not_actions",
+        &old.not_actions,
+        &new.not_actions,
+    );
+    diff_set(
+        changes,
+        location,
+        "resources",
+        &old.resources,
+        &new.resources,
+    );
+    diff_set(
+        changes,
+        location,
+        "not_resources",
+        &old.not_resources,
+        &new.not_resources,
+    );
+    diff_value(
+        changes,
+        &field("conditions"),
+        &old.conditions,
+        &new.conditions,
+    );
+    diff_value(
+        changes,
+        &field("principals"),
+        &old.principals,
+        &new.principals,
+    );
+    diff_value(
+        changes,
+        &field("not_principals"),
+        &old.not_principals,
+        &new.not_principals,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{managed_policy, miam, policy, statement, strings, user};
+
+    fn changes(old: &crate::Miam, new: &crate::Miam) -> Vec<String> {
+        super::diff(old, new)
+            .iter()
+            .map(|c| c.to_string())
+            .collect()
+    }
+
+    fn user_with(name: &str, groups: &[&str], actions: &[&str]) -> crate::User {
+        crate::User {
+            policies: vec![policy("default", vec![statement("Allow", actions, &["*"])])],
+            groups: strings(groups),
+            ..user(name)
+        }
+    }
+
+    fn with_statements(statements: Vec<crate::PolicyStatement>) -> crate::Miam {
+        let mut miam = miam();
+        miam.managed_policies.push(managed_policy("p", statements));
+        miam
+    }
+
+    fn with_sid(sid: &str, statement: crate::PolicyStatement) -> crate::PolicyStatement {
+        crate::PolicyStatement {
+            sid: Some(sid.to_owned()),
+            ..statement
+        }
+    }
+
+    #[test]
+    fn diff_users() {
+        let mut old = miam();
+        old.users.extend([
+            user_with("alice", &["admin", "viewer"], &["s3:Get*"]),
+            user_with("bob", &[], &["s3:Get*"]),
+        ]);
+        let mut new = miam();
+        new.users.extend([
+            user_with("carol", &[], &["s3:Get*"]),
+            user_with("alice", &["viewer", "developer"], &["s3:List*", "s3:Get*"]),
+        ]);
+        assert_eq!(
+            changes(&old, &new),
+            vec![
+                "- user(alice).groups(admin)",
+                "+ user(alice).groups(developer)",
+                "+ user(alice).policy(default).statements[0].actions(s3:List*)",
+                "- user(bob)",
+                "+ user(carol)",
+            ]
+        );
+    }
+
+    #[test]
+    fn statements_are_matched_by_content() {
+        let old = with_statements(vec![
+            statement("Allow", &["s3:GetObject", "s3:ListBucket"], &["*"]),
+            statement("Deny", &["s3:DeleteObject"], &["*"]),
+            statement("Allow", &["sqs:SendMessage"], &["*"]),
+        ]);
+        // Reordered statements and actions aren't changes
+        let new = with_statements(vec![
+            statement("Deny", &["s3:DeleteObject"], &["*"]),
+            statement("Allow", &["s3:ListBucket", "s3:GetObject"], &["*"]),
+            statement("Allow", &["sqs:SendMessage"], &["*"]),
+        ]);
+        assert!(changes(&old, &new).is_empty());
+
+        let new = with_statements(vec![
+            statement("Deny", &["s3:DeleteObject"], &["*"]),
+            statement("Allow", &["s3:GetObject"], &["*"]),
+        ]);
+        assert_eq!(
+            changes(&old, &new),
+            vec![
+                "- managed_policy(p).policy_document.statements[2]",
+                "- managed_policy(p).policy_document.statements[1].actions(s3:ListBucket)",
+            ]
+        );
+    }
+
+    #[test]
+    fn statements_are_matched_by_sid() {
+        let old = with_statements(vec![
+            with_sid("Read", statement("Allow", &["s3:GetObject"], &["*"])),
+            with_sid("Write", statement("Allow", &["s3:PutObject"], &["*"])),
+            statement("Deny", &["s3:DeleteObject"], &["*"]),
+        ]);
+        let new = with_statements(vec![
+            statement("Deny", &["s3:DeleteObject"], &["*"]),
+            with_sid(
+                "Read",
+                statement("Allow", &["s3:GetObject"], &["arn:aws:s3:::a/*"]),
+            ),
+            with_sid("List", statement("Allow", &["s3:ListBucket"], &["*"])),
+        ]);
+        assert_eq!(
+            changes(&old, &new),
+            vec![
+                "+ managed_policy(p).policy_document.statements(List)",
+                "- managed_policy(p).policy_document.statements(Write)",
+                "- managed_policy(p).policy_document.statements(Read).resources(*)",
+                "+ managed_policy(p).policy_document.statements(Read).resources(arn:aws:s3:::a/*)",
+            ]
+        );
+    }
+}
@@ -1,7 +1,11 @@
+pub mod check;
+pub mod diff;
 pub mod loader;
 pub mod mruby;
 mod mruby_c;
 pub mod printer;
+pub mod validate;
+pub mod who_can;
 
 #[derive(Debug)]
 pub struct Miam {
@@ -10,6 +14,43 @@ pub struct Miam {
     pub roles: Vec<Role>,
     pub managed_policies: Vec<ManagedPolicy>,
     pub instance_profiles: Vec<InstanceProfile>,
+    /// Names matching these patterns are not managed by IAMfile
+    pub excludes: Vec<regex::Regex>,
+}
+
+impl Miam {
+    pub fn is_excluded(&self, name: &str) -> bool {
+        self.excludes.iter().any(|pattern| pattern.is_match(name))
+    }
+
+    /// Find the customer managed policy defined in IAMfile by its ARN
+    pub fn find_managed_policy(&self, policy_arn: &str) -> Option<&ManagedPolicy> {
+        let (_, path_and_name) = policy_arn.split_once(":policy/")?;
+        let (path, name) = match path_and_name.rsplit_once('/') {
+            Some((path, name)) => (format!("/{}/", path), name),
+            None => ("/".to_owned(), path_and_name),
+        };
+        self.managed_policies
+            .iter()
+            .find(|policy| policy.name == name && policy.path.as_deref().unwrap_or("/") == path)
+    }
+
+    /// Remove principals, policies and instance profiles matching `excludes`
+    pub fn apply_excludes(&mut self) {
+        let excludes = &self.excludes;
+        let is_excluded = |name: &str| excludes.iter().any(|pattern| pattern.is_match(name));
+        self.users.retain(|user| !is_excluded(&user.user_name));
+        self.groups.retain(|group| !is_excluded(&group.name));
+        self.roles.retain(|role| !is_excluded(&role.name));
+        for role in &mut self.roles {
+            role.instance_profiles
+                .retain(|profile| !is_excluded(profile));
+        }
+        self.managed_policies
+            .retain(|policy| !is_excluded(&policy.name));
+        self.instance_profiles
+            .retain(|profile| !is_excluded(&profile.name));
+    }
 }
 
 #[derive(Debug)]
@@ -19,16 +60,18 @@ pub struct User {
     pub policies: Vec<PolicyDocument>,
     pub groups: Vec<String>,
     pub attached_managed_policies: Vec<String>,
+    pub permissions_boundary: Option<String>,
+    pub tags: std::collections::BTreeMap<String, String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct PolicyDocument {
     pub name: String,
     pub version: Option<String>,
     pub statements: Vec<PolicyStatement>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct PolicyStatement {
     pub sid: Option<String>,
     pub effect: String,
@@ -43,14 +86,14 @@ pub struct PolicyStatement {
     pub not_principals: Vec<PolicyPrincipal>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct PolicyCondition {
     pub test: String,
     pub variable: String,
     pub values: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct PolicyPrincipal {
     pub typ: String,
     pub identifiers: Vec<String>,
@@ -73,6 +116,22 @@ pub struct Role {
     pub attached_managed_policies: Vec<String>,
     pub instance_profiles: Vec<String>,
     pub max_session_duration: Option<i64>,
+    pub permissions_boundary: Option<String>,
+    pub tags: std::collections::BTreeMap<String, String>,
+    pub description: Option<String>,
+    pub force_detach_policies: bool,
+    /// Suffix of service-linked roles which services allow multiple roles of
+    pub custom_suffix: Option<String>,
+}
+
+impl Role {
+    /// Service-linked roles are created by AWS services under `/aws-service-role/<service>/`
+    pub fn service_linked_service_name(&self) -> Option<&str> {
+        self.path
+            .as_deref()?
+            .strip_prefix("/aws-service-role/")?
+            .strip_suffix('/')
+    }
 }
 
 #[derive(Debug)]
@@ -80,6 +139,8 @@ pub struct ManagedPolicy {
     pub name: String,
     pub path: Option<String>,
     pub policy_document: PolicyDocument,
+    pub description: Option<String>,
+    pub tags: std::collections::BTreeMap<String, String>,
 }
 
 #[derive(Debug)]
@@ -87,3 +148,95 @@ pub struct InstanceProfile {
     pub name: String,
     pub path: Option<String>,
 }
+
+/// Builders of the model for unit tests. Fields not given are empty
+#[cfg(test)]
+pub(crate) mod test {
+    pub fn miam() -> crate::Miam {
+        crate::Miam {
+            users: Vec::new(),
+            groups: Vec::new(),
+            roles: Vec::new(),
+            managed_policies: Vec::new(),
+            instance_profiles: Vec::new(),
+            excludes: Vec::new(),
+        }
+    }
+
+    pub fn user(name: &str) -> crate::User {
+        crate::User {
+            user_name: name.to_owned(),
+            path: None,
+            policies: Vec::new(),
+            groups: Vec::new(),
+            attached_managed_policies: Vec::new(),
+            permissions_boundary: None,
+            tags: Default::default(),
+        }
+    }
+
+    pub fn group(name: &str) -> crate::Group {
+        crate::Group {
+            name: name.to_owned(),
+            path: None,
+            policies: Vec::new(),
+            attached_managed_policies: Vec::new(),
+        }
+    }
+
+    pub fn role(name: &str) -> crate::Role {
+        crate::Role {
+            name: name.to_owned(),
+            path: None,
+            assume_role_policy_document: None,
+            policies: Vec::new(),
+            attached_managed_policies: Vec::new(),
+            instance_profiles: Vec::new(),
+            max_session_duration: None,
+            permissions_boundary: None,
+            tags: Default::default(),
+            description: None,
+            force_detach_policies: false,
+            custom_suffix: None,
+        }
+    }
+
+    pub fn managed_policy(
+        name: &str,
+        statements: Vec<crate::PolicyStatement>,
+    ) -> crate::ManagedPolicy {
+        crate::ManagedPolicy {
+            name: name.to_owned(),
+            path: None,
+            policy_document: policy(name, statements),
+            description: None,
+            tags: Default::default(),
+        }
+    }
+
+    pub fn policy(name: &str, statements: Vec<crate::PolicyStatement>) -> crate::PolicyDocument {
+        crate::PolicyDocument {
+            name: name.to_owned(),
+            version: None,
+            statements,
+        }
+    }
+
+    pub fn statement(effect: &str, actions: &[&str], resources: &[&str]) -> crate::PolicyStatement {
+        crate::PolicyStatement {
+            sid: None,
+            effect: effect.to_owned(),
+            actions: actions.iter().map(|s| s.to_string()).collect(),
+            resources: resources.iter().map(|s| s.to_string()).collect(),
+            conditions: Vec::new(),
+            principals: Vec::new(),
+            not_actions: Vec::new(),
+            not_resources: Vec::new(),
+            not_principals: Vec::new(),
+        }
+    }
+
+    pub fn strings(strings: &[&str]) -> Vec<String> {
+        strings.iter().map(|s| s.to_string()).collect()
+    }
+}
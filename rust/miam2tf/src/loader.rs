@@ -1,74 +1,202 @@
-pub fn load_miam<P>(path: P) -> Result<crate::Miam, anyhow::Error>
+#[derive(Debug)]
+pub enum Error {
+    Load(crate::mruby::Error),
+    Convert {
+        path: String,
+        source: crate::mruby::Error,
+    },
+    Exclude {
+        path: String,
+        source: regex::Error,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Load(e) => write!(f, "{}", e),
+            Self::Convert { path, source } => write!(f, "{}: {}", path, source),
+            Self::Exclude { path, source } => write!(f, "{}: {}", path, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Load(e) => e.source(),
+            Self::Convert { source, .. } => source.source(),
+            Self::Exclude { .. } => None,
+        }
+    }
+}
+
+/// Load IAMfile. Files outside of `root` cannot be read from IAMfile if it's given
+pub fn load_miam<P>(path: P, root: Option<&std::path::Path>) -> Result<crate::Miam, Error>
 where
     P: AsRef<std::path::Path>,
 {
     let mruby = crate::mruby::MRuby::default();
-    mruby.load(path)?;
+    if let Some(root) = root {
+        mruby.set_root(root).map_err(Error::Load)?;
+    }
+    mruby.load(path).map_err(Error::Load)?;
     let root = mruby.instance_variable_get("@root");
     to_miam(&root)
 }
 
-fn to_rust_policy_document(policy: &crate::mruby::Value) -> crate::PolicyDocument {
-    let name = policy.read_attribute("name").to_string();
-    let version = policy.read_attribute("version").to_string_opt();
-    let mut statements = Vec::new();
-    for statement in policy.read_attribute("statements").iter() {
-        let sid_value = statement.read_attribute("sid");
-        let sid = if sid_value.is_nil() {
-            None
-        } else {
-            Some(sid_value.to_string())
-        };
+fn attribute<'a>(
+    value: &crate::mruby::Value<'a>,
+    name: &'static str,
+    path: &str,
+) -> Result<crate::mruby::Value<'a>, Error> {
+    value.read_attribute(name).map_err(|source| Error::Convert {
+        path: format!("{}.{}", path, name),
+        source,
+    })
+}
 
-        let effect = statement.read_attribute("effect").to_string();
-        let mut actions = Vec::new();
-        for action in statement.read_attribute("actions").iter() {
-            actions.push(action.to_string());
-        }
-        let mut resources = Vec::new();
-        for resource in statement.read_attribute("resources").iter() {
-            resources.push(resource.to_string());
-        }
-        let mut not_actions = Vec::new();
-        for action in statement.read_attribute("not_actions").iter() {
-            not_actions.push(action.to_string());
-        }
-        let mut not_resources = Vec::new();
-        for resource in statement.read_attribute("not_resources").iter() {
-            not_resources.push(resource.to_string());
-        }
+fn convert_attribute<'a, T>(
+    value: &crate::mruby::Value<'a>,
+    name: &'static str,
+    path: &str,
+) -> Result<T, Error>
+where
+    T: TryFrom<crate::mruby::Value<'a>, Error = crate::mruby::Error>,
+{
+    T::try_from(attribute(value, name, path)?).map_err(|source| Error::Convert {
+        path: format!("{}.{}", path, name),
+        source,
+    })
+}
+
+fn convert_opt_attribute<'a, T>(
+    value: &crate::mruby::Value<'a>,
+    name: &'static str,
+    path: &str,
+) -> Result<Option<T>, Error>
+where
+    T: TryFrom<crate::mruby::Value<'a>, Error = crate::mruby::Error>,
+{
+    attribute(value, name, path)?
+        .to_opt()
+        .map_err(|source| Error::Convert {
+            path: format!("{}.{}", path, name),
+            source,
+        })
+}
+
+fn string_attribute(
+    value: &crate::mruby::Value,
+    name: &'static str,
+    path: &str,
+) -> Result<String, Error> {
+    convert_attribute(value, name, path)
+}
+
+fn string_opt_attribute(
+    value: &crate::mruby::Value,
+    name: &'static str,
+    path: &str,
+) -> Result<Option<String>, Error> {
+    convert_opt_attribute(value, name, path)
+}
+
+fn i64_opt_attribute(
+    value: &crate::mruby::Value,
+    name: &'static str,
+    path: &str,
+) -> Result<Option<i64>, Error> {
+    convert_opt_attribute(value, name, path)
+}
+
+fn array_attribute<'a>(
+    value: &crate::mruby::Value<'a>,
+    name: &'static str,
+    path: &str,
+) -> Result<crate::mruby::ValueIter<'a>, Error> {
+    attribute(value, name, path)?
+        .iter()
+        .map_err(|source| Error::Convert {
+            path: format!("{}.{}", path, name),
+            source,
+        })
+}
+
+fn strings_attribute(
+    value: &crate::mruby::Value,
+    name: &'static str,
+    path: &str,
+) -> Result<Vec<String>, Error> {
+    let mut strings = Vec::new();
+    for (i, s) in array_attribute(value, name, path)?.enumerate() {
+        strings.push(String::try_from(s).map_err(|source| Error::Convert {
+            path: format!("{}.{}[{}]", path, name, i),
+            source,
+        })?);
+    }
+    Ok(strings)
+}
+
+fn tags_attribute(
+    value: &crate::mruby::Value,
+    path: &str,
+) -> Result<std::collections::BTreeMap<String, String>, Error> {
+    let mut tags = std::collections::BTreeMap::new();
+    for (i, tag) in array_attribute(value, "tags", path)?.enumerate() {
+        let path = format!("{}.tags[{}]", path, i);
+        let key = string_attribute(&tag, "key", &path)?;
+        let value = string_attribute(&tag, "value", &path)?;
+        tags.insert(key, value);
+    }
+    Ok(tags)
+}
+
+fn to_rust_principals(
+    statement: &crate::mruby::Value,
+    name: &'static str,
+    path: &str,
+) -> Result<Vec<crate::PolicyPrincipal>, Error> {
+    let mut principals = Vec::new();
+    for (i, principal) in array_attribute(statement, name, path)?.enumerate() {
+        let path = format!("{}.{}[{}]", path, name, i);
+        let typ = string_attribute(&principal, "type", &path)?;
+        let identifiers = strings_attribute(&principal, "identifiers", &path)?;
+        principals.push(crate::PolicyPrincipal { typ, identifiers });
+    }
+    Ok(principals)
+}
+
+fn to_rust_policy_document(
+    policy: &crate::mruby::Value,
+    path: &str,
+) -> Result<crate::PolicyDocument, Error> {
+    let name = string_attribute(policy, "name", path)?;
+    let path = format!("{}({})", path, name);
+    let version = string_opt_attribute(policy, "version", &path)?;
+    let mut statements = Vec::new();
+    for (i, statement) in array_attribute(policy, "statements", &path)?.enumerate() {
+        let path = format!("{}.statements[{}]", path, i);
+        let sid = string_opt_attribute(&statement, "sid", &path)?;
+        let effect = string_attribute(&statement, "effect", &path)?;
+        let actions = strings_attribute(&statement, "actions", &path)?;
+        let resources = strings_attribute(&statement, "resources", &path)?;
+        let not_actions = strings_attribute(&statement, "not_actions", &path)?;
+        let not_resources = strings_attribute(&statement, "not_resources", &path)?;
         let mut conditions = Vec::new();
-        for condition in statement.read_attribute("conditions").iter() {
-            let test = condition.read_attribute("test").to_string();
-            let variable = condition.read_attribute("variable").to_string();
-            let mut values = Vec::new();
-            for value in condition.read_attribute("values").iter() {
-                values.push(value.to_string());
-            }
+        for (j, condition) in array_attribute(&statement, "conditions", &path)?.enumerate() {
+            let path = format!("{}.conditions[{}]", path, j);
+            let test = string_attribute(&condition, "test", &path)?;
+            let variable = string_attribute(&condition, "variable", &path)?;
+            let values = strings_attribute(&condition, "values", &path)?;
             conditions.push(crate::PolicyCondition {
                 test,
                 variable,
                 values,
             });
         }
-        let mut principals = Vec::new();
-        for principal in statement.read_attribute("principals").iter() {
-            let typ = principal.read_attribute("type").to_string();
-            let mut identifiers = Vec::new();
-            for identifier in principal.read_attribute("identifiers").iter() {
-                identifiers.push(identifier.to_string());
-            }
-            principals.push(crate::PolicyPrincipal { typ, identifiers });
-        }
-        let mut not_principals = Vec::new();
-        for principal in statement.read_attribute("not_principals").iter() {
-            let typ = principal.read_attribute("type").to_string();
-            let mut identifiers = Vec::new();
-            for identifier in principal.read_attribute("identifiers").iter() {
-                identifiers.push(identifier.to_string());
-            }
-            not_principals.push(crate::PolicyPrincipal { typ, identifiers });
-        }
+        let principals = to_rust_principals(&statement, "principals", &path)?;
+        let not_principals = to_rust_principals(&statement, "not_principals", &path)?;
         statements.push(crate::PolicyStatement {
             sid,
             effect,
@@ -82,116 +210,212 @@ fn to_rust_policy_document(policy: &crate::mruby::Value) -> crate::PolicyDocumen
             not_principals,
         });
     }
-    crate::PolicyDocument {
+    Ok(crate::PolicyDocument {
         name,
         version,
         statements,
+    })
+}
+
+fn to_rust_policy_documents(
+    value: &crate::mruby::Value,
+    path: &str,
+) -> Result<Vec<crate::PolicyDocument>, Error> {
+    let mut policies = Vec::new();
+    for (i, policy) in array_attribute(value, "policies", path)?.enumerate() {
+        policies.push(to_rust_policy_document(
+            &policy,
+            &format!("{}.policies[{}]", path, i),
+        )?);
     }
+    Ok(policies)
 }
 
-fn to_miam(root: &crate::mruby::Value) -> Result<crate::Miam, anyhow::Error> {
+fn to_miam(root: &crate::mruby::Value) -> Result<crate::Miam, Error> {
     let mut users = Vec::new();
-    for user in root.read_attribute("users").iter() {
-        let user_name = user.read_attribute("user_name").to_string();
-        let path = user.read_attribute("path").to_string_opt();
-        let mut policies = Vec::new();
-        for policy in user.read_attribute("policies").iter() {
-            policies.push(to_rust_policy_document(&policy));
-        }
-        let mut groups = Vec::new();
-        for group in user.read_attribute("groups").iter() {
-            groups.push(group.to_string());
-        }
-        let mut attached_managed_policies = Vec::new();
-        for policy in user.read_attribute("attached_managed_policies").iter() {
-            attached_managed_policies.push(policy.to_string());
-        }
+    for (i, user) in array_attribute(root, "users", "@root")?.enumerate() {
+        let user_name = string_attribute(&user, "user_name", &format!("users[{}]", i))?;
+        let path = format!("user({})", user_name);
+        let iam_path = string_opt_attribute(&user, "path", &path)?;
+        let policies = to_rust_policy_documents(&user, &path)?;
+        let groups = strings_attribute(&user, "groups", &path)?;
+        let attached_managed_policies =
+            strings_attribute(&user, "attached_managed_policies", &path)?;
+        let permissions_boundary = string_opt_attribute(&user, "permissions_boundary", &path)?;
+        let tags = tags_attribute(&user, &path)?;
         users.push(crate::User {
             user_name,
-            path,
+            path: iam_path,
             policies,
             groups,
             attached_managed_policies,
+            permissions_boundary,
+            tags,
         });
     }
 
     let mut groups = Vec::new();
-    for group in root.read_attribute("groups").iter() {
-        let name = group.read_attribute("name").to_string();
-        let path = group.read_attribute("path").to_string_opt();
-        let mut policies = Vec::new();
-        for policy in group.read_attribute("policies").iter() {
-            policies.push(to_rust_policy_document(&policy));
-        }
-        let mut attached_managed_policies = Vec::new();
-        for policy in group.read_attribute("attached_managed_policies").iter() {
-            attached_managed_policies.push(policy.to_string());
-        }
+    for (i, group) in array_attribute(root, "groups", "@root")?.enumerate() {
+        let name = string_attribute(&group, "name", &format!("groups[{}]", i))?;
+        let path = format!("group({})", name);
+        let iam_path = string_opt_attribute(&group, "path", &path)?;
+        let policies = to_rust_policy_documents(&group, &path)?;
+        let attached_managed_policies =
+            strings_attribute(&group, "attached_managed_policies", &path)?;
         groups.push(crate::Group {
             name,
-            path,
+            path: iam_path,
             policies,
             attached_managed_policies,
         });
     }
+
     let mut roles = Vec::new();
-    for role in root.read_attribute("roles").iter() {
-        let name = role.read_attribute("name").to_string();
-        let path = role.read_attribute("path").to_string_opt();
-        let assume_role_policy_document = role.read_attribute("assume_role_policy_document");
+    for (i, role) in array_attribute(root, "roles", "@root")?.enumerate() {
+        let name = string_attribute(&role, "name", &format!("roles[{}]", i))?;
+        let path = format!("role({})", name);
+        let iam_path = string_opt_attribute(&role, "path", &path)?;
+        let assume_role_policy_document = attribute(&role, "assume_role_policy_document", &path)?;
         let assume_role_policy_document = if assume_role_policy_document.is_nil() {
             None
         } else {
-            Some(to_rust_policy_document(&assume_role_policy_document))
+            Some(to_rust_policy_document(
+                &assume_role_policy_document,
+                &format!("{}.assume_role_policy_document", path),
+            )?)
         };
-        let mut policies = Vec::new();
-        for policy in role.read_attribute("policies").iter() {
-            policies.push(to_rust_policy_document(&policy));
-        }
-        let mut attached_managed_policies = Vec::new();
-        for policy in role.read_attribute("attached_managed_policies").iter() {
-            attached_managed_policies.push(policy.to_string());
-        }
-        let mut instance_profiles = Vec::new();
-        for profile in role.read_attribute("instance_profiles").iter() {
-            instance_profiles.push(profile.to_string());
-        }
-        let max_session_duration = role.read_attribute("max_session_duration").to_i64_opt();
+        let policies = to_rust_policy_documents(&role, &path)?;
+        let attached_managed_policies =
+            strings_attribute(&role, "attached_managed_policies", &path)?;
+        let instance_profiles = strings_attribute(&role, "instance_profiles", &path)?;
+        let max_session_duration = i64_opt_attribute(&role, "max_session_duration", &path)?;
+        let permissions_boundary = string_opt_attribute(&role, "permissions_boundary", &path)?;
+        let tags = tags_attribute(&role, &path)?;
+        let description = string_opt_attribute(&role, "description", &path)?;
+        let force_detach_policies = attribute(&role, "force_detach_policies", &path)?.is_truthy();
+        let custom_suffix = string_opt_attribute(&role, "custom_suffix", &path)?;
         roles.push(crate::Role {
             name,
-            path,
+            path: iam_path,
             assume_role_policy_document,
             policies,
             attached_managed_policies,
             max_session_duration,
             instance_profiles,
+            permissions_boundary,
+            tags,
+            description,
+            force_detach_policies,
+            custom_suffix,
         });
     }
 
     let mut managed_policies = Vec::new();
-    for policy in root.read_attribute("managed_policies").iter() {
-        let name = policy.read_attribute("name").to_string();
-        let path = policy.read_attribute("path").to_string_opt();
-        let policy_document = to_rust_policy_document(&policy.read_attribute("policy_document"));
+    for (i, policy) in array_attribute(root, "managed_policies", "@root")?.enumerate() {
+        let name = string_attribute(&policy, "name", &format!("managed_policies[{}]", i))?;
+        let path = format!("managed_policy({})", name);
+        let iam_path = string_opt_attribute(&policy, "path", &path)?;
+        let policy_document = to_rust_policy_document(
+            &attribute(&policy, "policy_document", &path)?,
+            &format!("{}.policy_document", path),
+        )?;
+        let description = string_opt_attribute(&policy, "description", &path)?;
+        let tags = tags_attribute(&policy, &path)?;
         managed_policies.push(crate::ManagedPolicy {
             name,
-            path,
+            path: iam_path,
             policy_document,
+            description,
+            tags,
         });
     }
 
     let mut instance_profiles = Vec::new();
-    for profile in root.read_attribute("instance_profiles").iter() {
-        let name = profile.read_attribute("name").to_string();
-        let path = profile.read_attribute("path").to_string_opt();
+    for (i, profile) in array_attribute(root, "instance_profiles", "@root")?.enumerate() {
+        let name = string_attribute(&profile, "name", &format!("instance_profiles[{}]", i))?;
+        let path = string_opt_attribute(&profile, "path", &format!("instance_profile({})", name))?;
         instance_profiles.push(crate::InstanceProfile { name, path });
     }
 
-    Ok(crate::Miam {
+    let mut excludes = Vec::new();
+    for (i, pattern) in strings_attribute(root, "excludes", "@root")?
+        .into_iter()
+        .enumerate()
+    {
+        let pattern =
+            regex::Regex::new(&ruby_regex_to_rust(&pattern)).map_err(|source| Error::Exclude {
+                path: format!("excludes[{}]", i),
+                source,
+            })?;
+        excludes.push(pattern);
+    }
+
+    let mut miam = crate::Miam {
         users,
         groups,
         roles,
         managed_policies,
         instance_profiles,
-    })
+        excludes,
+    };
+    miam.apply_excludes();
+    Ok(miam)
+}
+
+/// Convert the source of Ruby's Regexp to the syntax of regex crate. Most of the syntax is shared
+/// (`\A`, `\z`, `(?<name>...)`, `(?i)`, character classes, ...), and `\h`, `\H` and `\Z` are
+/// converted here. Other syntax only Ruby supports, such as lookaround and backreferences, is
+/// reported as an error by regex crate
+fn ruby_regex_to_rust(source: &str) -> String {
+    let mut converted = String::with_capacity(source.len());
+    let mut in_class = false;
+    let mut chars = source.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('h') if in_class => converted.push_str("0-9a-fA-F"),
+                Some('h') => converted.push_str("[0-9a-fA-F]"),
+                Some('H') if !in_class => converted.push_str("[^0-9a-fA-F]"),
+                // Matching the final newline is the same as matching before it for is_match
+                Some('Z') if !in_class => converted.push_str("\\n?\\z"),
+                Some(c) => {
+                    converted.push('\\');
+                    converted.push(c);
+                }
+                None => converted.push('\\'),
+            },
+            '[' => {
+                in_class = true;
+                converted.push(c);
+            }
+            ']' => {
+                in_class = false;
+                converted.push(c);
+            }
+            _ => converted.push(c),
+        }
+    }
+    converted
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn ruby_regex_to_rust() {
+        for (ruby, rust) in [
+            (r"\Aadmin-\d+\z", r"\Aadmin-\d+\z"),
+            (r"(?<env>dev|stg)-\h{8}", r"(?<env>dev|stg)-[0-9a-fA-F]{8}"),
+            (r"[\h_]+\Z", r"[0-9a-fA-F_]+\n?\z"),
+            (r"\H\[\h\]", r"[^0-9a-fA-F]\[[0-9a-fA-F]\]"),
+        ] {
+            assert_eq!(super::ruby_regex_to_rust(ruby), rust);
+        }
+        let pattern =
+            regex::Regex::new(&super::ruby_regex_to_rust(r"\A(?<env>dev|stg)-\h+\Z")).unwrap();
+        assert!(pattern.is_match("dev-c0ffee"));
+        assert!(!pattern.is_match("prd-c0ffee"));
+        assert!(!pattern.is_match("dev-coffee"));
+        // Lookahead isn't supported by regex crate
+        assert!(regex::Regex::new(&super::ruby_regex_to_rust(r"admin(?!-ro)")).is_err());
+    }
 }
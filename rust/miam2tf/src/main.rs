@@ -1,5 +1,141 @@
+#[derive(Debug, clap::Parser)]
+struct Args {
+    /// Path to IAMfile
+    #[clap(short, long, default_value = "IAMfile")]
+    file: std::path::PathBuf,
+    /// Forbid IAMfile to read files outside of this directory
+    #[clap(long)]
+    root: Option<std::path::PathBuf>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Convert IAMfile to Terraform definitions (default)
+    Convert(ConvertArgs),
+    /// Report problems in IAMfile without converting it
+    Check,
+    /// Report changes between two IAMfiles. Exit with status 1 if there are changes and 2 on
+    /// errors
+    Diff {
+        old: std::path::PathBuf,
+        new: std::path::PathBuf,
+    },
+    /// List users and roles allowed to perform the action
+    WhoCan {
+        /// Action such as s3:GetObject
+        action: String,
+        /// Resource ARN. Any resource is matched if omitted
+        resource: Option<String>,
+    },
+}
+
+#[derive(Debug, Default, clap::Args)]
+struct ConvertArgs {
+    /// Module path where the generated resources are placed (e.g. module.iam).
+    /// Requires --imports-output since import and moved blocks must be placed in the root module
+    #[clap(long, requires = "imports_output")]
+    module: Option<String>,
+    /// Write import and moved blocks to this file instead of stdout
+    #[clap(long)]
+    imports_output: Option<std::path::PathBuf>,
+    /// Address mapping file written by the previous --write-addresses to generate moved blocks
+    #[clap(long)]
+    previous_addresses: Option<std::path::PathBuf>,
+    /// Write the address mapping of the generated resources to this file
+    #[clap(long)]
+    write_addresses: Option<std::path::PathBuf>,
+}
+
 fn main() -> Result<(), anyhow::Error> {
-    let miam = miam2tf::loader::load_miam("IAMfile")?;
-    miam2tf::printer::print_as_hcl2(&mut std::io::stdout().lock(), &miam)?;
+    use clap::Parser as _;
+    let args = Args::parse();
+
+    let load = |path: &std::path::Path| miam2tf::loader::load_miam(path, args.root.as_deref());
+    match args
+        .command
+        .unwrap_or_else(|| Command::Convert(ConvertArgs::default()))
+    {
+        Command::Convert(convert_args) => {
+            let miam = load(&args.file)?;
+            miam2tf::validate::validate(&miam)?;
+            convert(&miam, convert_args)?;
+        }
+        Command::Check => {
+            miam2tf::check::check(&load(&args.file)?)?;
+        }
+        Command::Diff { old, new } => {
+            // Status 1 means changes like diff(1)
+            let (old, new) = match load(&old).and_then(|old| Ok((old, load(&new)?))) {
+                Ok(miams) => miams,
+                Err(e) => {
+                    eprintln!("Error: {:?}", anyhow::Error::from(e));
+                    std::process::exit(2);
+                }
+            };
+            let changes = miam2tf::diff::diff(&old, &new);
+            for change in &changes {
+                println!("{}", change);
+            }
+            if !changes.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Command::WhoCan { action, resource } => {
+            let miam = load(&args.file)?;
+            for grant in miam2tf::who_can::who_can(&miam, &action, resource.as_deref()) {
+                println!("{}", grant);
+            }
+        }
+    }
     Ok(())
 }
+
+fn convert(miam: &miam2tf::Miam, args: ConvertArgs) -> Result<(), anyhow::Error> {
+    let previous_addresses = if let Some(path) = args.previous_addresses {
+        let file = std::fs::File::open(path)?;
+        miam2tf::printer::read_addresses(std::io::BufReader::new(file))?
+    } else {
+        Vec::new()
+    };
+    let options = miam2tf::printer::Options {
+        module: args.module,
+        previous_addresses,
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    let imports = miam2tf::printer::print_resources(&mut stdout, miam)?;
+    if let Some(path) = args.imports_output {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        miam2tf::printer::print_imports(&mut file, &imports, &options)?;
+    } else {
+        miam2tf::printer::print_imports(&mut stdout, &imports, &options)?;
+    }
+    if let Some(path) = args.write_addresses {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        miam2tf::printer::write_addresses(&mut file, &imports, &options)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn module_requires_imports_output() {
+        use clap::Parser as _;
+
+        let e = super::Args::try_parse_from(["miam2tf", "convert", "--module", "module.iam"])
+            .unwrap_err();
+        assert_eq!(e.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        assert!(super::Args::try_parse_from([
+            "miam2tf",
+            "convert",
+            "--module",
+            "module.iam",
+            "--imports-output",
+            "imports.tf",
+        ])
+        .is_ok());
+    }
+}
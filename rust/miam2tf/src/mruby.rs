@@ -1,22 +1,111 @@
-use std::io::Read as _;
-
 pub struct MRuby {
     mrb: *mut crate::mruby_c::mrb_state,
+    files: Box<std::cell::RefCell<Files>>,
+}
+
+/// Files loaded by `require`, which are resolved relative to the requiring file
+#[derive(Debug, Default)]
+struct Files {
+    /// Files outside of this directory cannot be read
+    root: Option<std::path::PathBuf>,
+    loaded: std::collections::HashSet<std::path::PathBuf>,
+    /// Files being evaluated, the last one is the current file
+    loading: Vec<LoadingFile>,
+}
+
+#[derive(Debug)]
+struct LoadingFile {
+    canonical_path: std::path::PathBuf,
+    /// Path shown in backtraces and used to resolve relative paths
+    path: std::path::PathBuf,
 }
 
+/// Ruby value protected from GC while it's alive.
+///
+/// Methods that call into mruby VM must not be used from native methods defined in Rust.
 pub struct Value<'a> {
     mruby: &'a MRuby,
     inner: crate::mruby_c::mrb_value,
+    /// Whether the value is registered to GC roots, or only protected by the GC arena
+    registered: bool,
+}
+
+/// Ruby exception raised while evaluating code in the embedded interpreter
+#[derive(Debug)]
+pub struct Exception {
+    pub class: String,
+    pub message: String,
+    pub backtrace: Vec<String>,
+}
+
+impl std::fmt::Display for Exception {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut backtrace = self.backtrace.iter();
+        if let Some(location) = backtrace.next() {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{} ({})", self.message, self.class)?;
+        for location in backtrace {
+            write!(f, "\n\tfrom {}", location)?;
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for Exception {}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Exception(Exception),
+    TypeMismatch {
+        expected: &'static str,
+        actual: String,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Exception(e) => write!(f, "{}", e),
+            Self::TypeMismatch { expected, actual } => {
+                write!(f, "expected {} but got {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => e.source(),
+            Self::Exception(_) | Self::TypeMismatch { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<Exception> for Error {
+    fn from(e: Exception) -> Self {
+        Self::Exception(e)
+    }
 }
 
 impl Default for MRuby {
     fn default() -> Self {
+        let files = Box::new(std::cell::RefCell::new(Files::default()));
         let mrb = unsafe {
             let mrb = crate::mruby_c::mrb_open();
+            (*mrb).ud = &*files as *const std::cell::RefCell<Files> as *mut std::ffi::c_void;
             add_missing_methods(mrb);
             mrb
         };
-        Self { mrb }
+        Self { mrb, files }
     }
 }
 
@@ -29,48 +118,273 @@ impl Drop for MRuby {
 }
 
 impl MRuby {
-    pub fn load<P>(&self, path: P) -> Result<(), anyhow::Error>
+    /// Restrict files read by `require`, `Dir.glob` and `File.read` to the directory
+    pub fn set_root<P>(&self, root: P) -> Result<(), Error>
     where
         P: AsRef<std::path::Path>,
     {
-        let path_cstr = std::ffi::CString::new(format!("{}", path.as_ref().display()))?;
-        let mut file = std::fs::File::open(path)?;
-        let mut code = Vec::new();
-        file.read_to_end(&mut code)?;
+        self.files.borrow_mut().root = Some(std::fs::canonicalize(root)?);
+        Ok(())
+    }
 
+    pub fn load<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let path = path.as_ref();
+        let (file, code) = self.files.borrow().read(path, path)?;
+        let path_str = format!("{}", path.display());
+        {
+            let mut files = self.files.borrow_mut();
+            files.loaded.insert(file.canonical_path.clone());
+            files.loading.push(file);
+        }
+        let result = self.load_code(&code, &path_str);
+        self.files.borrow_mut().loading.pop();
+        result?;
+        Ok(())
+    }
+
+    /// Evaluate `code` and return the last value
+    pub fn eval(&self, code: &str) -> Result<Value<'_>, Error> {
+        self.load_code(code.as_bytes(), "(eval)")
+    }
+
+    fn load_code(&self, code: &[u8], filename: &str) -> Result<Value<'_>, Error> {
+        let filename_cstr = std::ffi::CString::new(filename)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        self.assert_not_in_native_method();
+        self.with_arena(|| {
+            let value = unsafe {
+                let ctx = crate::mruby_c::mrbc_context_new(self.mrb);
+                crate::mruby_c::mrbc_filename(self.mrb, ctx, filename_cstr.as_ptr());
+                let value = crate::mruby_c::mrb_load_nstring_cxt(
+                    self.mrb,
+                    code.as_ptr() as *const i8,
+                    code.len(),
+                    ctx,
+                );
+                crate::mruby_c::mrbc_context_free(self.mrb, ctx);
+                value
+            };
+            if let Some(mut exception) = self.take_exception() {
+                // SyntaxError doesn't have backtrace
+                if exception.backtrace.is_empty() {
+                    exception.backtrace.push(filename.to_owned());
+                }
+                return Err(Error::Exception(exception));
+            }
+            Ok(Value::new(self, value))
+        })
+    }
+
+    pub fn top_self(&self) -> Value<'_> {
+        Value::new(self, unsafe {
+            crate::mruby_c::wrapper_mrb_obj_value((*self.mrb).top_self)
+        })
+    }
+
+    pub fn instance_variable_get(&self, name: &str) -> Value<'_> {
+        self.with_arena(|| {
+            let value = unsafe {
+                crate::mruby_c::mrb_obj_iv_get(self.mrb, (*self.mrb).top_self, self.intern(name))
+            };
+            Value::new(self, value)
+        })
+    }
+
+    pub fn nil(&self) -> Value<'_> {
+        Value::new(self, mrb_nil_value())
+    }
+
+    /// Convert Rust value to Ruby value
+    pub fn value<T>(&self, value: T) -> Value<'_>
+    where
+        T: IntoValue,
+    {
+        self.with_arena(|| value.into_value(self).register())
+    }
+
+    fn intern(&self, name: &str) -> crate::mruby_c::mrb_sym {
+        unsafe { crate::mruby_c::mrb_intern(self.mrb, name.as_ptr() as *const i8, name.len()) }
+    }
+
+    /// Objects created by C API are kept in the GC arena until it's restored. Since Rust code
+    /// isn't called from mruby VM, the arena is never restored automatically and grows
+    /// unboundedly without this. Values returned from `f` must be registered by [`Value::new`].
+    fn with_arena<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let idx = unsafe { crate::mruby_c::wrapper_mrb_gc_arena_save(self.mrb) };
+        let ret = f();
+        unsafe { crate::mruby_c::wrapper_mrb_gc_arena_restore(self.mrb, idx) };
+        ret
+    }
+
+    /// Exceptions raised inside native methods are propagated by longjmp, which must not cross
+    /// Rust frames. Exceptions are converted to `Result` only when called outside mruby VM.
+    fn assert_not_in_native_method(&self) {
+        assert!(
+            unsafe { (*self.mrb).jmp.is_null() },
+            "mruby API must not be called from native methods"
+        );
+    }
+
+    /// Clear the pending exception and convert it to Rust value
+    fn take_exception(&self) -> Option<Exception> {
+        let exc = unsafe { (*self.mrb).exc };
+        if exc.is_null() {
+            return None;
+        }
+        let exception = self.with_arena(|| {
+            // Keep the exception reachable from the GC arena before clearing it
+            let exc = Value::temporary(self, unsafe { crate::mruby_c::wrapper_mrb_obj_value(exc) });
+            unsafe {
+                (*self.mrb).exc = std::ptr::null_mut();
+            }
+            let exc = exc.inner;
+            let class = class_name(self.mrb, exc);
+            let message = unsafe { funcall0(self.mrb, exc, "message") };
+            let message = if mrb_string_p(message) {
+                to_rust_string(message)
+            } else {
+                class.clone()
+            };
+            let mut backtrace = Vec::new();
+            let locations = unsafe { funcall0(self.mrb, exc, "backtrace") };
+            if mrb_array_p(locations) {
+                for i in 0..rarray_len(locations) {
+                    let location = unsafe { crate::mruby_c::mrb_ary_entry(locations, i) };
+                    if mrb_string_p(location) {
+                        backtrace.push(to_rust_string(location));
+                    }
+                }
+            }
+            Exception {
+                class,
+                message,
+                backtrace,
+            }
+        });
+        // Ignore exceptions raised while inspecting the exception
         unsafe {
-            let ctx = crate::mruby_c::mrbc_context_new(self.mrb);
-            crate::mruby_c::mrbc_filename(self.mrb, ctx, path_cstr.as_ptr());
-            crate::mruby_c::mrb_load_nstring_cxt(
-                self.mrb,
-                code.as_ptr() as *const i8,
-                code.len(),
-                ctx,
-            );
-            crate::mruby_c::mrbc_context_free(self.mrb, ctx);
-            if !(*self.mrb).exc.is_null() {
-                crate::mruby_c::mrb_print_error(self.mrb);
-                return Err(anyhow::anyhow!("MRuby error"));
+            (*self.mrb).exc = std::ptr::null_mut();
+        }
+        Some(exception)
+    }
+}
+
+impl Files {
+    /// Resolve `path` relative to the current file
+    fn resolve(&self, path: &str) -> std::path::PathBuf {
+        let path = std::path::Path::new(path);
+        match self.current_dir() {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_owned(),
+        }
+    }
+
+    fn current_dir(&self) -> Option<&std::path::Path> {
+        self.loading
+            .last()
+            .and_then(|file| file.path.parent())
+            .filter(|dir| !dir.as_os_str().is_empty())
+    }
+
+    fn check_root(&self, path: &std::path::Path) -> Result<std::path::PathBuf, std::io::Error> {
+        let canonical_path = std::fs::canonicalize(path)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        if let Some(ref root) = self.root {
+            if !canonical_path.starts_with(root) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    format!("{}: outside of {}", path.display(), root.display()),
+                ));
             }
         }
-        Ok(())
+        Ok(canonical_path)
+    }
+
+    fn read(
+        &self,
+        path: &std::path::Path,
+        display_path: &std::path::Path,
+    ) -> Result<(LoadingFile, Vec<u8>), std::io::Error> {
+        let canonical_path = self.check_root(path)?;
+        let code = std::fs::read(&canonical_path)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        Ok((
+            LoadingFile {
+                canonical_path,
+                path: display_path.to_owned(),
+            },
+            code,
+        ))
+    }
+
+    /// Returns `None` if the file has been already loaded
+    fn start_require(&mut self, path: &str) -> Result<Option<(String, Vec<u8>)>, std::io::Error> {
+        let resolved = self.resolve(path);
+        let canonical_path = self.check_root(&resolved)?;
+        if let Some(i) = self
+            .loading
+            .iter()
+            .position(|file| file.canonical_path == canonical_path)
+        {
+            let mut cycle: Vec<_> = self.loading[i..]
+                .iter()
+                .map(|file| format!("{}", file.path.display()))
+                .collect();
+            cycle.push(format!("{}", resolved.display()));
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("circular require: {}", cycle.join(" -> ")),
+            ));
+        }
+        if self.loaded.contains(&canonical_path) {
+            return Ok(None);
+        }
+        let (file, code) = self.read(&resolved, &resolved)?;
+        let path_str = format!("{}", file.path.display());
+        self.loaded.insert(file.canonical_path.clone());
+        self.loading.push(file);
+        Ok(Some((path_str, code)))
     }
 
-    pub fn instance_variable_get<'a>(&'a self, name: &'static str) -> Value<'a> {
-        let value = unsafe {
-            crate::mruby_c::mrb_obj_iv_get(
-                self.mrb,
-                (*self.mrb).top_self,
-                crate::mruby_c::mrb_intern_static(self.mrb, name.as_ptr() as *const i8, name.len()),
-            )
+    /// Expand the pattern relative to the current file. Returned paths are also relative to it
+    fn glob(&self, pattern: &str) -> Result<Vec<String>, std::io::Error> {
+        let dir = self.current_dir().filter(|_| !pattern.starts_with('/'));
+        let pattern = match dir {
+            Some(dir) => format!(
+                "{}/{}",
+                glob::Pattern::escape(&format!("{}", dir.display())),
+                pattern
+            ),
+            None => pattern.to_owned(),
         };
-        Value {
-            mruby: self,
-            inner: value,
+        let entries = glob::glob(&pattern)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(std::io::Error::from)?;
+            self.check_root(&path)?;
+            let relative_path = match dir {
+                Some(dir) => path.strip_prefix(dir).unwrap_or(&path),
+                None => &path,
+            };
+            paths.push(format!("{}", relative_path.display()));
         }
+        Ok(paths)
     }
 }
 
+/// # Safety
+/// `mrb` must be opened by [`MRuby`]
+unsafe fn files<'a>(mrb: *mut crate::mruby_c::mrb_state) -> &'a std::cell::RefCell<Files> {
+    &*((*mrb).ud as *const std::cell::RefCell<Files>)
+}
+
 unsafe fn add_missing_methods(mrb: *mut crate::mruby_c::mrb_state) {
     let dir_class =
         crate::mruby_c::mrb_define_class(mrb, c"Dir".as_ptr(), (*mrb).object_class);
@@ -88,6 +402,23 @@ unsafe fn add_missing_methods(mrb: *mut crate::mruby_c::mrb_state) {
         Some(mrb_require),
         mrb_args_req(1),
     );
+    let file_class =
+        crate::mruby_c::mrb_define_class(mrb, c"File".as_ptr(), (*mrb).object_class);
+    crate::mruby_c::mrb_define_class_method(
+        mrb,
+        file_class,
+        c"read".as_ptr(),
+        Some(mrb_file_read),
+        mrb_args_req(1),
+    );
+    let json_module = crate::mruby_c::mrb_define_module(mrb, c"JSON".as_ptr());
+    crate::mruby_c::mrb_define_module_function(
+        mrb,
+        json_module,
+        c"parse".as_ptr(),
+        Some(mrb_json_parse),
+        mrb_args_req(1),
+    );
 }
 
 fn mrb_args_req(n: i64) -> crate::mruby_c::mrb_aspec {
@@ -106,6 +437,47 @@ fn mrb_nil_value() -> crate::mruby_c::mrb_value {
     unsafe { crate::mruby_c::wrapper_mrb_nil_value() }
 }
 
+fn mrb_string_p(o: crate::mruby_c::mrb_value) -> bool {
+    unsafe { crate::mruby_c::wrapper_mrb_string_p(o) != 0 }
+}
+
+fn mrb_array_p(o: crate::mruby_c::mrb_value) -> bool {
+    unsafe { crate::mruby_c::wrapper_mrb_array_p(o) != 0 }
+}
+
+fn mrb_integer_p(o: crate::mruby_c::mrb_value) -> bool {
+    unsafe { crate::mruby_c::wrapper_mrb_integer_p(o) != 0 }
+}
+
+fn mrb_hash_p(o: crate::mruby_c::mrb_value) -> bool {
+    unsafe { crate::mruby_c::wrapper_mrb_hash_p(o) != 0 }
+}
+
+fn mrb_true_p(o: crate::mruby_c::mrb_value) -> bool {
+    unsafe { crate::mruby_c::wrapper_mrb_true_p(o) != 0 }
+}
+
+fn mrb_false_p(o: crate::mruby_c::mrb_value) -> bool {
+    unsafe { crate::mruby_c::wrapper_mrb_false_p(o) != 0 }
+}
+
+fn class_name(mrb: *mut crate::mruby_c::mrb_state, o: crate::mruby_c::mrb_value) -> String {
+    unsafe {
+        std::ffi::CStr::from_ptr(crate::mruby_c::mrb_obj_classname(mrb, o))
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+unsafe fn funcall0(
+    mrb: *mut crate::mruby_c::mrb_state,
+    recv: crate::mruby_c::mrb_value,
+    name: &'static str,
+) -> crate::mruby_c::mrb_value {
+    let meth = crate::mruby_c::mrb_intern_static(mrb, name.as_ptr() as *const i8, name.len());
+    crate::mruby_c::mrb_funcall_argv(mrb, recv, meth, 0, std::ptr::null())
+}
+
 fn unwrap_or_raise<T, E>(mrb: *mut crate::mruby_c::mrb_state, r: Result<T, E>) -> T
 where
     E: std::error::Error,
@@ -113,7 +485,7 @@ where
     match r {
         Ok(v) => v,
         Err(e) => {
-            let msg = std::ffi::CString::new(format!("{:?}", e)).unwrap();
+            let msg = std::ffi::CString::new(format!("{}", e).replace('\0', "\\0")).unwrap();
             unsafe {
                 crate::mruby_c::mrb_raise(
                     mrb,
@@ -134,14 +506,16 @@ extern "C" fn mrb_dir_glob(
     let pat = unsafe {
         let mut val = mrb_nil_value();
         crate::mruby_c::mrb_get_args(mrb, c"S&".as_ptr(), &mut val, &mut block);
-        to_rust_string(mrb, val)
+        to_rust_string(val)
     };
 
+    // Borrows must be released before raising since longjmp skips destructors
+    let paths = unsafe { files(mrb) }.borrow().glob(&pat);
+    drop(pat);
+    let paths = unwrap_or_raise(mrb, paths);
     if mrb_nil_p(block) {
         let entries = unsafe { crate::mruby_c::mrb_ary_new(mrb) };
-        for entry in unwrap_or_raise(mrb, glob::glob(&pat)) {
-            let path = unwrap_or_raise(mrb, entry);
-            let path_str = format!("{}", path.display());
+        for path_str in paths {
             unsafe {
                 let path_value = crate::mruby_c::mrb_str_new(
                     mrb,
@@ -153,9 +527,7 @@ extern "C" fn mrb_dir_glob(
         }
         entries
     } else {
-        for entry in unwrap_or_raise(mrb, glob::glob(&pat)) {
-            let path = unwrap_or_raise(mrb, entry);
-            let path_str = format!("{}", path.display());
+        for path_str in paths {
             unsafe {
                 let path_value = crate::mruby_c::mrb_str_new(
                     mrb,
@@ -186,49 +558,126 @@ extern "C" fn mrb_require(
     mrb: *mut crate::mruby_c::mrb_state,
     _self: crate::mruby_c::mrb_value,
 ) -> crate::mruby_c::mrb_value {
-    let path = unsafe {
+    let feature = unsafe {
         let mut val = mrb_nil_value();
         crate::mruby_c::mrb_get_args(mrb, c"S".as_ptr(), &mut val);
-        to_rust_string(mrb, val)
+        to_rust_string(val)
     };
-    let mut file = unwrap_or_raise(mrb, std::fs::File::open(&path));
-    let mut code = Vec::new();
-    unwrap_or_raise(mrb, file.read_to_end(&mut code));
-    unsafe {
-        let path_cstr = unwrap_or_raise(mrb, std::ffi::CString::new(path.as_bytes()));
+    let required = unsafe { files(mrb) }.borrow_mut().start_require(&feature);
+    drop(feature);
+    let required = unwrap_or_raise(mrb, required);
+    let Some((path, code)) = required else {
+        return unsafe { crate::mruby_c::wrapper_mrb_bool_value(0) };
+    };
+    let exc = unsafe {
+        let path_cstr = std::ffi::CString::new(path).unwrap_or_default();
         let ctx = crate::mruby_c::mrbc_context_new(mrb);
         crate::mruby_c::mrbc_filename(mrb, ctx, path_cstr.as_ptr());
         crate::mruby_c::mrb_load_nstring_cxt(mrb, code.as_ptr() as *const i8, code.len(), ctx);
         crate::mruby_c::mrbc_context_free(mrb, ctx);
+        files(mrb).borrow_mut().loading.pop();
+        (*mrb).exc
+    };
+    drop(code);
+    if !exc.is_null() {
+        // Propagate exceptions raised in the required file to the caller
+        unsafe {
+            (*mrb).exc = std::ptr::null_mut();
+            crate::mruby_c::mrb_exc_raise(mrb, crate::mruby_c::wrapper_mrb_obj_value(exc));
+        }
     }
-    mrb_nil_value()
+    unsafe { crate::mruby_c::wrapper_mrb_bool_value(1) }
+}
+
+extern "C" fn mrb_file_read(
+    mrb: *mut crate::mruby_c::mrb_state,
+    _self: crate::mruby_c::mrb_value,
+) -> crate::mruby_c::mrb_value {
+    let path = unsafe {
+        let mut val = mrb_nil_value();
+        crate::mruby_c::mrb_get_args(mrb, c"S".as_ptr(), &mut val);
+        to_rust_string(val)
+    };
+    let content = unwrap_or_raise(mrb, {
+        let files = unsafe { files(mrb) }.borrow();
+        let path = files.resolve(&path);
+        files.read(&path, &path).map(|(_, content)| content)
+    });
+    unsafe { crate::mruby_c::mrb_str_new(mrb, content.as_ptr() as *const i8, content.len()) }
+}
+
+extern "C" fn mrb_json_parse(
+    mrb: *mut crate::mruby_c::mrb_state,
+    _self: crate::mruby_c::mrb_value,
+) -> crate::mruby_c::mrb_value {
+    let source = unsafe {
+        let mut val = mrb_nil_value();
+        crate::mruby_c::mrb_get_args(mrb, c"S".as_ptr(), &mut val);
+        to_rust_string(val)
+    };
+    let json = unwrap_or_raise(mrb, serde_json::from_str(&source));
+    json_to_value(mrb, &json)
 }
 
-fn to_rust_string(mrb: *mut crate::mruby_c::mrb_state, s: crate::mruby_c::mrb_value) -> String {
+fn json_to_value(
+    mrb: *mut crate::mruby_c::mrb_state,
+    json: &serde_json::Value,
+) -> crate::mruby_c::mrb_value {
     unsafe {
-        std::ffi::CStr::from_ptr(crate::mruby_c::mrb_str_to_cstr(mrb, s))
-            .to_string_lossy()
-            .into_owned()
+        match json {
+            serde_json::Value::Null => mrb_nil_value(),
+            serde_json::Value::Bool(b) => crate::mruby_c::wrapper_mrb_bool_value(*b as u8),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => crate::mruby_c::wrapper_mrb_int_value(mrb, i),
+                None => {
+                    crate::mruby_c::wrapper_mrb_float_value(mrb, n.as_f64().unwrap_or(f64::NAN))
+                }
+            },
+            serde_json::Value::String(s) => {
+                crate::mruby_c::mrb_str_new(mrb, s.as_ptr() as *const i8, s.len())
+            }
+            serde_json::Value::Array(values) => {
+                let ary = crate::mruby_c::mrb_ary_new(mrb);
+                for value in values {
+                    crate::mruby_c::mrb_ary_push(mrb, ary, json_to_value(mrb, value));
+                }
+                ary
+            }
+            serde_json::Value::Object(entries) => {
+                let hash = crate::mruby_c::mrb_hash_new(mrb);
+                for (key, value) in entries {
+                    let key_value =
+                        crate::mruby_c::mrb_str_new(mrb, key.as_ptr() as *const i8, key.len());
+                    crate::mruby_c::mrb_hash_set(mrb, hash, key_value, json_to_value(mrb, value));
+                }
+                hash
+            }
+        }
+    }
+}
+
+/// `s` must be a String
+fn to_rust_string(s: crate::mruby_c::mrb_value) -> String {
+    unsafe {
+        let ptr = crate::mruby_c::wrapper_rstring_ptr(s) as *const u8;
+        let len = crate::mruby_c::wrapper_rstring_len(s) as usize;
+        String::from_utf8_lossy(std::slice::from_raw_parts(ptr, len)).into_owned()
     }
 }
 
 pub struct ValueIter<'a> {
     idx: i64,
     len: i64,
-    ary: crate::mruby_c::mrb_value,
-    mruby: &'a MRuby,
+    ary: Value<'a>,
 }
 impl<'a> Iterator for ValueIter<'a> {
     type Item = Value<'a>;
 
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
         if self.idx < self.len {
-            let val = unsafe { crate::mruby_c::mrb_ary_entry(self.ary, self.idx) };
+            let val = unsafe { crate::mruby_c::mrb_ary_entry(self.ary.inner, self.idx) };
             self.idx += 1;
-            Some(Value {
-                mruby: self.mruby,
-                inner: val,
-            })
+            Some(Value::new(self.ary.mruby, val))
         } else {
             None
         }
@@ -236,56 +685,465 @@ impl<'a> Iterator for ValueIter<'a> {
 }
 
 impl<'a> Value<'a> {
-    pub fn read_attribute(&self, name: &'static str) -> Value<'a> {
-        let value = unsafe {
-            let meth = crate::mruby_c::mrb_intern_static(
-                self.mruby.mrb,
-                name.as_ptr() as *const i8,
-                name.len(),
-            );
-            crate::mruby_c::mrb_funcall_argv(self.mruby.mrb, self.inner, meth, 0, std::ptr::null())
-        };
-        Value {
-            mruby: self.mruby,
-            inner: value,
+    /// Values held in Rust are registered to GC roots so that they aren't collected
+    fn new(mruby: &'a MRuby, inner: crate::mruby_c::mrb_value) -> Self {
+        unsafe { crate::mruby_c::mrb_gc_register(mruby.mrb, inner) };
+        Self {
+            mruby,
+            inner,
+            registered: true,
+        }
+    }
+
+    /// Value protected only by the GC arena, which must not outlive the enclosing
+    /// [`MRuby::with_arena`]. Unregistering scans all GC roots, so values used only while
+    /// converting are kept in the arena instead.
+    fn temporary(mruby: &'a MRuby, inner: crate::mruby_c::mrb_value) -> Self {
+        unsafe { crate::mruby_c::mrb_gc_protect(mruby.mrb, inner) };
+        Self {
+            mruby,
+            inner,
+            registered: false,
+        }
+    }
+
+    /// Register the value to GC roots so that it can outlive the GC arena
+    fn register(self) -> Self {
+        if self.registered {
+            self
+        } else {
+            Value::new(self.mruby, self.inner)
         }
     }
 
+    /// Call the method with arguments
+    pub fn call(&self, method: &str, args: &[Value<'a>]) -> Result<Value<'a>, Error> {
+        self.mruby.assert_not_in_native_method();
+        let argv: Vec<_> = args.iter().map(|arg| arg.inner).collect();
+        self.mruby.with_arena(|| {
+            let value = unsafe {
+                crate::mruby_c::mrb_funcall_argv(
+                    self.mruby.mrb,
+                    self.inner,
+                    self.mruby.intern(method),
+                    argv.len() as i64,
+                    argv.as_ptr(),
+                )
+            };
+            if let Some(exception) = self.mruby.take_exception() {
+                return Err(Error::Exception(exception));
+            }
+            Ok(Value::new(self.mruby, value))
+        })
+    }
+
+    pub fn read_attribute(&self, name: &str) -> Result<Value<'a>, Error> {
+        self.call(name, &[])
+    }
+
     pub fn is_nil(&self) -> bool {
         mrb_nil_p(self.inner)
     }
 
-    #[allow(clippy::inherent_to_string)]
-    pub fn to_string(&self) -> String {
-        to_rust_string(self.mruby.mrb, self.inner)
+    pub fn is_truthy(&self) -> bool {
+        unsafe { crate::mruby_c::wrapper_mrb_test(self.inner) != 0 }
+    }
+
+    pub fn class_name(&self) -> String {
+        self.mruby
+            .with_arena(|| class_name(self.mruby.mrb, self.inner))
     }
 
-    pub fn to_string_opt(&self) -> Option<String> {
+    fn type_mismatch(&self, expected: &'static str) -> Error {
+        Error::TypeMismatch {
+            expected,
+            actual: self.class_name(),
+        }
+    }
+
+    /// Convert to `T`, or `None` if the value is nil
+    pub fn to_opt<T>(self) -> Result<Option<T>, Error>
+    where
+        T: TryFrom<Value<'a>, Error = Error>,
+    {
         if self.is_nil() {
-            None
+            Ok(None)
         } else {
-            Some(self.to_string())
+            T::try_from(self).map(Some)
         }
     }
 
-    pub fn to_i64(&self) -> i64 {
-        unsafe { crate::mruby_c::wrapper_mrb_integer(self.inner) }
+    pub fn iter(&self) -> Result<ValueIter<'a>, Error> {
+        if mrb_array_p(self.inner) {
+            Ok(ValueIter {
+                idx: 0,
+                len: rarray_len(self.inner),
+                ary: self.clone(),
+            })
+        } else {
+            Err(self.type_mismatch("Array"))
+        }
     }
+}
 
-    pub fn to_i64_opt(&self) -> Option<i64> {
-        if self.is_nil() {
-            None
+impl Clone for Value<'_> {
+    fn clone(&self) -> Self {
+        Value::new(self.mruby, self.inner)
+    }
+}
+
+impl Drop for Value<'_> {
+    fn drop(&mut self) {
+        if self.registered {
+            unsafe { crate::mruby_c::mrb_gc_unregister(self.mruby.mrb, self.inner) };
+        }
+    }
+}
+
+/// Only strings and immediate values are shown since calling `inspect` may raise, which
+/// longjmps through Rust frames when called from native methods
+impl std::fmt::Debug for Value<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if mrb_nil_p(self.inner) {
+            write!(f, "Value(nil)")
+        } else if mrb_true_p(self.inner) {
+            write!(f, "Value(true)")
+        } else if mrb_false_p(self.inner) {
+            write!(f, "Value(false)")
+        } else if mrb_integer_p(self.inner) {
+            write!(f, "Value({})", unsafe {
+                crate::mruby_c::wrapper_mrb_integer(self.inner)
+            })
+        } else if mrb_string_p(self.inner) {
+            write!(f, "Value({:?})", to_rust_string(self.inner))
+        } else {
+            write!(f, "Value(#<{}>)", self.class_name())
+        }
+    }
+}
+
+impl TryFrom<Value<'_>> for String {
+    type Error = Error;
+
+    fn try_from(value: Value<'_>) -> Result<Self, Self::Error> {
+        if mrb_string_p(value.inner) {
+            Ok(to_rust_string(value.inner))
+        } else {
+            Err(value.type_mismatch("String"))
+        }
+    }
+}
+
+impl TryFrom<Value<'_>> for i64 {
+    type Error = Error;
+
+    fn try_from(value: Value<'_>) -> Result<Self, Self::Error> {
+        if mrb_integer_p(value.inner) {
+            Ok(unsafe { crate::mruby_c::wrapper_mrb_integer(value.inner) })
         } else {
-            Some(self.to_i64())
+            Err(value.type_mismatch("Integer"))
         }
     }
+}
+
+impl TryFrom<Value<'_>> for bool {
+    type Error = Error;
 
-    pub fn iter(&self) -> ValueIter<'a> {
-        ValueIter {
-            idx: 0,
-            len: rarray_len(self.inner),
-            ary: self.inner,
-            mruby: self.mruby,
+    fn try_from(value: Value<'_>) -> Result<Self, Self::Error> {
+        if mrb_true_p(value.inner) {
+            Ok(true)
+        } else if mrb_false_p(value.inner) {
+            Ok(false)
+        } else {
+            Err(value.type_mismatch("true or false"))
         }
     }
 }
+
+impl<'a, T> TryFrom<Value<'a>> for Vec<T>
+where
+    T: TryFrom<Value<'a>, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        if !mrb_array_p(value.inner) {
+            return Err(value.type_mismatch("Array"));
+        }
+        let mruby = value.mruby;
+        mruby.with_arena(|| {
+            (0..rarray_len(value.inner))
+                .map(|i| {
+                    T::try_from(Value::temporary(mruby, unsafe {
+                        crate::mruby_c::mrb_ary_entry(value.inner, i)
+                    }))
+                })
+                .collect()
+        })
+    }
+}
+
+impl<'a, K, V> TryFrom<Value<'a>> for std::collections::HashMap<K, V>
+where
+    K: TryFrom<Value<'a>, Error = Error> + Eq + std::hash::Hash,
+    V: TryFrom<Value<'a>, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        hash_try_from(&value)
+    }
+}
+
+impl<'a, K, V> TryFrom<Value<'a>> for std::collections::BTreeMap<K, V>
+where
+    K: TryFrom<Value<'a>, Error = Error> + Ord,
+    V: TryFrom<Value<'a>, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        hash_try_from(&value)
+    }
+}
+
+fn hash_try_from<'a, K, V, C>(value: &Value<'a>) -> Result<C, Error>
+where
+    K: TryFrom<Value<'a>, Error = Error>,
+    V: TryFrom<Value<'a>, Error = Error>,
+    C: FromIterator<(K, V)>,
+{
+    if !mrb_hash_p(value.inner) {
+        return Err(value.type_mismatch("Hash"));
+    }
+    let mruby = value.mruby;
+    mruby.with_arena(|| {
+        let keys = Value::temporary(mruby, unsafe {
+            crate::mruby_c::mrb_hash_keys(mruby.mrb, value.inner)
+        });
+        (0..rarray_len(keys.inner))
+            .map(|i| {
+                let (k, v) = unsafe {
+                    let k = crate::mruby_c::mrb_ary_entry(keys.inner, i);
+                    (k, crate::mruby_c::mrb_hash_get(mruby.mrb, value.inner, k))
+                };
+                Ok((
+                    K::try_from(Value::temporary(mruby, k))?,
+                    V::try_from(Value::temporary(mruby, v))?,
+                ))
+            })
+            .collect()
+    })
+}
+
+/// Conversion from Rust values to Ruby values, which are only protected by the GC arena until
+/// [`MRuby::value`] registers them
+pub trait IntoValue {
+    fn into_value(self, mruby: &MRuby) -> Value<'_>;
+}
+
+impl IntoValue for &str {
+    fn into_value(self, mruby: &MRuby) -> Value<'_> {
+        Value::temporary(mruby, unsafe {
+            crate::mruby_c::mrb_str_new(mruby.mrb, self.as_ptr() as *const i8, self.len())
+        })
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self, mruby: &MRuby) -> Value<'_> {
+        self.as_str().into_value(mruby)
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self, mruby: &MRuby) -> Value<'_> {
+        Value::temporary(mruby, unsafe {
+            crate::mruby_c::wrapper_mrb_int_value(mruby.mrb, self)
+        })
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self, mruby: &MRuby) -> Value<'_> {
+        Value::temporary(mruby, unsafe {
+            crate::mruby_c::wrapper_mrb_bool_value(self as u8)
+        })
+    }
+}
+
+impl<T> IntoValue for Vec<T>
+where
+    T: IntoValue,
+{
+    fn into_value(self, mruby: &MRuby) -> Value<'_> {
+        let ary = Value::temporary(mruby, unsafe { crate::mruby_c::mrb_ary_new(mruby.mrb) });
+        for elem in self {
+            let elem = elem.into_value(mruby);
+            unsafe { crate::mruby_c::mrb_ary_push(mruby.mrb, ary.inner, elem.inner) };
+        }
+        ary
+    }
+}
+
+#[cfg(test)]
+mod test {
+    fn tempdir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("users")).unwrap();
+        std::fs::write(dir.path().join("IAMfile"), "").unwrap();
+        std::fs::write(dir.path().join("users/a.iam"), "").unwrap();
+        std::fs::write(dir.path().join("users/b.iam"), "").unwrap();
+        dir
+    }
+
+    fn start(files: &mut super::Files, path: &std::path::Path) {
+        let (file, _) = files.read(path, path).unwrap();
+        files.loaded.insert(file.canonical_path.clone());
+        files.loading.push(file);
+    }
+
+    #[test]
+    fn require_relative_to_current_file() {
+        let tempdir = tempdir();
+        let dir = tempdir.path();
+        let mut files = super::Files::default();
+        start(&mut files, &dir.join("IAMfile"));
+
+        let mut paths = files.glob("users/*.iam").unwrap();
+        paths.sort();
+        assert_eq!(paths, vec!["users/a.iam", "users/b.iam"]);
+
+        let (path, _) = files.start_require("users/a.iam").unwrap().unwrap();
+        assert_eq!(path, format!("{}", dir.join("users/a.iam").display()));
+        assert_eq!(files.glob("b.iam").unwrap(), vec!["b.iam"]);
+
+        // users/a.iam requires IAMfile
+        let err = files.start_require("../IAMfile").unwrap_err();
+        assert!(err.to_string().starts_with("circular require: "), "{}", err);
+
+        files.loading.pop();
+        assert!(files.start_require("users/a.iam").unwrap().is_none());
+    }
+
+    #[test]
+    fn forbid_files_outside_of_root() {
+        let tempdir = tempdir();
+        let dir = tempdir.path();
+        let mut files = super::Files {
+            root: Some(std::fs::canonicalize(dir.join("users")).unwrap()),
+            ..Default::default()
+        };
+        start(&mut files, &dir.join("users/a.iam"));
+
+        assert!(files.start_require("b.iam").unwrap().is_some());
+        let err = files.start_require("../IAMfile").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        let err = files.glob("../*").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn eval_and_call() {
+        let mruby = super::MRuby::default();
+        let value = mruby.eval("[1, 2].map { |i| i * 2 }").unwrap();
+        assert_eq!(Vec::<i64>::try_from(value).unwrap(), vec![2, 4]);
+
+        let value = mruby.value("abc").call("upcase", &[]).unwrap();
+        assert_eq!(String::try_from(value).unwrap(), "ABC");
+        let value = mruby.value(1).call("+", &[mruby.value(2)]).unwrap();
+        assert_eq!(i64::try_from(value).unwrap(), 3);
+
+        match mruby.eval("raise ArgumentError, 'bad'").unwrap_err() {
+            super::Error::Exception(e) => {
+                assert_eq!(e.class, "ArgumentError");
+                assert_eq!(e.message, "bad");
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+        let err = mruby.value(1).call("undefined", &[]).unwrap_err();
+        assert!(matches!(err, super::Error::Exception(ref e) if e.class == "NoMethodError"));
+        // The interpreter is still usable after exceptions
+        assert!(mruby.eval("true").unwrap().is_truthy());
+    }
+
+    #[test]
+    fn try_from() {
+        let mruby = super::MRuby::default();
+        assert_eq!(String::try_from(mruby.value("a")).unwrap(), "a");
+        assert_eq!(i64::try_from(mruby.value(-1)).unwrap(), -1);
+        assert!(bool::try_from(mruby.value(true)).unwrap());
+        assert!(!bool::try_from(mruby.value(false)).unwrap());
+        assert_eq!(mruby.nil().to_opt::<String>().unwrap(), None);
+        assert_eq!(
+            mruby.value("a").to_opt::<String>().unwrap(),
+            Some("a".to_owned())
+        );
+
+        let err = String::try_from(mruby.value(1)).unwrap_err();
+        assert_eq!(err.to_string(), "expected String but got Integer");
+        let err = bool::try_from(mruby.nil()).unwrap_err();
+        assert_eq!(err.to_string(), "expected true or false but got NilClass");
+        let err = Vec::<String>::try_from(mruby.eval(r#"["a", 1]"#).unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "expected String but got Integer");
+        let err = Vec::<String>::try_from(mruby.value("a")).unwrap_err();
+        assert_eq!(err.to_string(), "expected Array but got String");
+    }
+
+    #[test]
+    fn hash() {
+        let mruby = super::MRuby::default();
+        let value = mruby.eval(r#"{ "b" => 2, "a" => 1 }"#).unwrap();
+        let map = std::collections::BTreeMap::<String, i64>::try_from(value.clone()).unwrap();
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            vec![("a".to_owned(), 1), ("b".to_owned(), 2)]
+        );
+        let map = std::collections::HashMap::<String, i64>::try_from(value.clone()).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["b"], 2);
+
+        let err = std::collections::HashMap::<String, String>::try_from(value).unwrap_err();
+        assert_eq!(err.to_string(), "expected String but got Integer");
+        let err = std::collections::BTreeMap::<String, String>::try_from(mruby.eval("[]").unwrap())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "expected Hash but got Array");
+    }
+
+    #[test]
+    fn into_value() {
+        let mruby = super::MRuby::default();
+        let value = mruby.value(vec!["a".to_owned(), "b".to_owned()]);
+        assert_eq!(value.class_name(), "Array");
+        let joined = value.call("join", &[mruby.value(",")]).unwrap();
+        assert_eq!(String::try_from(joined).unwrap(), "a,b");
+        assert_eq!(
+            Vec::<Vec<i64>>::try_from(mruby.value(vec![vec![1], vec![2, 3]])).unwrap(),
+            vec![vec![1], vec![2, 3]]
+        );
+        assert!(mruby.value(true).is_truthy());
+        assert!(!mruby.value(false).is_truthy());
+        assert!(mruby.nil().is_nil());
+
+        // Values outlive the GC arena and survive GC
+        let values: Vec<_> = (0..1000).map(|i| mruby.value(format!("s{}", i))).collect();
+        mruby.eval("GC.start").unwrap();
+        for (i, value) in values.into_iter().enumerate() {
+            assert_eq!(String::try_from(value).unwrap(), format!("s{}", i));
+        }
+    }
+
+    #[test]
+    fn debug() {
+        let mruby = super::MRuby::default();
+        assert_eq!(format!("{:?}", mruby.nil()), "Value(nil)");
+        assert_eq!(format!("{:?}", mruby.value(true)), "Value(true)");
+        assert_eq!(format!("{:?}", mruby.value(1)), "Value(1)");
+        assert_eq!(format!("{:?}", mruby.value("a\"b")), r#"Value("a\"b")"#);
+        // inspect isn't called
+        let value = mruby
+            .eval("o = Object.new; def o.inspect; raise 'inspect'; end; o")
+            .unwrap();
+        assert_eq!(format!("{:?}", value), "Value(#<Object>)");
+    }
+}
@@ -1,27 +1,99 @@
+/// Terraform `import {}` block of a generated resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub resource_type: String,
+    pub name: String,
+    pub id: String,
+}
+
+impl Import {
+    fn new(resource_type: &str, name: &str, id: String) -> Self {
+        Self {
+            resource_type: resource_type.to_owned(),
+            name: name.to_owned(),
+            id,
+        }
+    }
+
+    /// Resource address in the root module
+    pub fn address(&self, module: Option<&str>) -> String {
+        match module {
+            Some(module) => format!("{}.{}.{}", module, self.resource_type, self.name),
+            None => format!("{}.{}", self.resource_type, self.name),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Module path where the generated resources are placed, e.g. `module.iam`
+    pub module: Option<String>,
+    /// Resource addresses generated previously. `moved {}` blocks are generated for resources
+    /// whose addresses are changed
+    pub previous_addresses: Vec<PreviousAddress>,
+}
+
+/// An entry of the address mapping file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviousAddress {
+    pub address: String,
+    pub id: String,
+}
+
+impl PreviousAddress {
+    fn resource_type(&self) -> Option<&str> {
+        let mut parts = self.address.rsplit('.');
+        parts.next()?;
+        parts.next()
+    }
+}
+
+/// Print resources and `import {}` blocks
 pub fn print_as_hcl2<W>(writer: &mut W, miam: &crate::Miam) -> Result<(), std::io::Error>
 where
     W: std::io::Write,
 {
-    for user in &miam.users {
+    let imports = print_resources(writer, miam)?;
+    print_imports(writer, &imports, &Options::default())
+}
+
+/// Print resources and return `import {}` blocks for them.
+/// `import {}` blocks must be placed in the root module while the resources can be placed in a
+/// child module.
+pub fn print_resources<W>(writer: &mut W, miam: &crate::Miam) -> Result<Vec<Import>, std::io::Error>
+where
+    W: std::io::Write,
+{
+    let mut imports = Vec::new();
+    for user in sorted_by_name(&miam.users, |u| &u.user_name) {
         writeln!(writer, r#"resource "aws_iam_user" "{}" {{"#, user.user_name)?;
-        writeln!(writer, r#"  name = "{}""#, user.user_name)?;
+        writeln!(writer, "  name = {}", hcl_string(&user.user_name))?;
         if let Some(ref path) = user.path {
-            writeln!(writer, r#"  path = "{}""#, path)?;
+            writeln!(writer, "  path = {}", hcl_string(path))?;
+        }
+        if let Some(ref policy_arn) = user.permissions_boundary {
+            writeln!(
+                writer,
+                "  permissions_boundary = {}",
+                hcl_string(policy_arn)
+            )?;
         }
+        print_tags(writer, &user.tags)?;
         writeln!(writer, "}}")?;
 
-        writeln!(writer, "import {{")?;
-        writeln!(writer, "  to = aws_iam_user.{}", user.user_name)?;
-        writeln!(writer, r#"  id = "{}""#, user.user_name)?;
-        writeln!(writer, "}}")?;
+        imports.push(Import::new(
+            "aws_iam_user",
+            &user.user_name,
+            user.user_name.clone(),
+        ));
 
-        for policy in &user.policies {
+        for policy in sorted_by_name(&user.policies, |p| &p.name) {
             writeln!(
                 writer,
                 r#"resource "aws_iam_user_policy" "{}-{}" {{"#,
                 user.user_name, policy.name
             )?;
-            writeln!(writer, r#"  name = "{}""#, policy.name)?;
+            writeln!(writer, "  name = {}", hcl_string(&policy.name))?;
             writeln!(writer, "  user = aws_iam_user.{}.name", user.user_name)?;
             writeln!(
                 writer,
@@ -30,14 +102,11 @@ where
             )?;
             writeln!(writer, "}}")?;
 
-            writeln!(writer, "import {{")?;
-            writeln!(
-                writer,
-                "  to = aws_iam_user_policy.{}-{}",
-                user.user_name, policy.name
-            )?;
-            writeln!(writer, r#"  id = "{}:{}""#, user.user_name, policy.name)?;
-            writeln!(writer, "}}")?;
+            imports.push(Import::new(
+                "aws_iam_user_policy",
+                &format!("{}-{}", user.user_name, policy.name),
+                format!("{}:{}", user.user_name, policy.name),
+            ));
 
             print_policy_document(
                 writer,
@@ -46,6 +115,7 @@ where
             )?;
         }
         if !user.groups.is_empty() {
+            let groups = sorted(&user.groups);
             writeln!(
                 writer,
                 r#"resource "aws_iam_user_group_membership" "{}" {{"#,
@@ -53,32 +123,37 @@ where
             )?;
             writeln!(writer, "  user = aws_iam_user.{}.name", user.user_name)?;
             writeln!(writer, "  groups = [")?;
-            for group in &user.groups {
-                writeln!(writer, "    aws_iam_group.{}.name,", group)?;
+            for group in &groups {
+                if miam.is_excluded(group) {
+                    writeln!(writer, "    {},", hcl_string(group))?;
+                } else {
+                    writeln!(writer, "    aws_iam_group.{}.name,", group)?;
+                }
             }
             writeln!(writer, "  ]")?;
             writeln!(writer, "}}")?;
 
-            writeln!(writer, "import {{")?;
-            writeln!(
-                writer,
-                "  to = aws_iam_user_group_membership.{}",
-                user.user_name
-            )?;
-            write!(writer, r#"  id = "{}"#, user.user_name)?;
-            for group in &user.groups {
-                write!(writer, r#"/{group}"#)?;
+            let mut id = user.user_name.clone();
+            for group in &groups {
+                id.push('/');
+                id.push_str(group);
             }
-            writeln!(writer, "\"")?;
-            writeln!(writer, "}}")?;
+            imports.push(Import::new(
+                "aws_iam_user_group_membership",
+                &user.user_name,
+                id,
+            ));
         }
-        for policy in &user.attached_managed_policies {
-            let short_policy_name = policy.rsplit_once('/').map(|(_, x)| x).unwrap_or_else(|| {
-                panic!(
-                    "Invalid attached_managed_policies {} found in {} user",
-                    policy, user.user_name
+        for policy in sorted(&user.attached_managed_policies) {
+            let short_policy_name = short_policy_name(policy).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Invalid attached_managed_policies {} found in {} user",
+                        policy, user.user_name
+                    ),
                 )
-            });
+            })?;
             writeln!(
                 writer,
                 r#"resource "aws_iam_user_policy_attachment" "{}-{}" {{"#,
@@ -86,8 +161,8 @@ where
             )?;
             writeln!(writer, "  user = aws_iam_user.{}.name", user.user_name)?;
             let aws_managed = policy.starts_with("arn:aws:iam::aws:policy/");
-            if aws_managed {
-                writeln!(writer, r#"  policy_arn = "{policy}""#)?;
+            if aws_managed || miam.is_excluded(short_policy_name) {
+                writeln!(writer, "  policy_arn = {}", hcl_string(policy))?;
             } else {
                 writeln!(
                     writer,
@@ -95,37 +170,35 @@ where
                 )?;
             }
             writeln!(writer, "}}")?;
-            writeln!(writer, "import {{")?;
-            writeln!(
-                writer,
-                "  to = aws_iam_user_policy_attachment.{}-{}",
-                user.user_name, short_policy_name
-            )?;
-            writeln!(writer, r#"  id = "{}/{}""#, user.user_name, policy)?;
-            writeln!(writer, "}}")?;
+            imports.push(Import::new(
+                "aws_iam_user_policy_attachment",
+                &format!("{}-{}", user.user_name, short_policy_name),
+                format!("{}/{}", user.user_name, policy),
+            ));
         }
     }
 
-    for group in &miam.groups {
+    for group in sorted_by_name(&miam.groups, |g| &g.name) {
         writeln!(writer, r#"resource "aws_iam_group" "{}" {{"#, group.name)?;
-        writeln!(writer, r#"  name = "{}""#, group.name)?;
+        writeln!(writer, "  name = {}", hcl_string(&group.name))?;
         if let Some(ref path) = group.path {
-            writeln!(writer, r#"  path = "{}""#, path)?;
+            writeln!(writer, "  path = {}", hcl_string(path))?;
         }
         writeln!(writer, "}}")?;
 
-        writeln!(writer, "import {{")?;
-        writeln!(writer, "  to = aws_iam_group.{}", group.name)?;
-        writeln!(writer, r#"  id = "{}""#, group.name)?;
-        writeln!(writer, "}}")?;
+        imports.push(Import::new(
+            "aws_iam_group",
+            &group.name,
+            group.name.clone(),
+        ));
 
-        for policy in &group.policies {
+        for policy in sorted_by_name(&group.policies, |p| &p.name) {
             writeln!(
                 writer,
                 r#"resource "aws_iam_group_policy" "{}-{}" {{"#,
                 group.name, policy.name
             )?;
-            writeln!(writer, r#"  name = "{}""#, policy.name)?;
+            writeln!(writer, "  name = {}", hcl_string(&policy.name))?;
             writeln!(writer, "  group = aws_iam_group.{}.name", group.name)?;
             writeln!(
                 writer,
@@ -134,24 +207,24 @@ where
             )?;
             writeln!(writer, "}}")?;
 
-            writeln!(writer, "import {{")?;
-            writeln!(
-                writer,
-                "  to = aws_iam_group_policy.{}-{}",
-                group.name, policy.name
-            )?;
-            writeln!(writer, r#"  id = "{}:{}""#, group.name, policy.name)?;
-            writeln!(writer, "}}")?;
+            imports.push(Import::new(
+                "aws_iam_group_policy",
+                &format!("{}-{}", group.name, policy.name),
+                format!("{}:{}", group.name, policy.name),
+            ));
 
             print_policy_document(writer, &format!("{}-{}", group.name, policy.name), policy)?;
         }
-        for policy in &group.attached_managed_policies {
-            let short_policy_name = policy.rsplit_once('/').map(|(_, x)| x).unwrap_or_else(|| {
-                panic!(
-                    "Invalid attached_managed_policies {} found in {} group",
-                    policy, group.name
+        for policy in sorted(&group.attached_managed_policies) {
+            let short_policy_name = short_policy_name(policy).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Invalid attached_managed_policies {} found in {} group",
+                        policy, group.name
+                    ),
                 )
-            });
+            })?;
             writeln!(
                 writer,
                 r#"resource "aws_iam_group_policy_attachment" "{}-{}" {{"#,
@@ -159,8 +232,8 @@ where
             )?;
             writeln!(writer, "  group = aws_iam_group.{}.name", group.name)?;
             let aws_managed = policy.starts_with("arn:aws:iam::aws:policy/");
-            if aws_managed {
-                writeln!(writer, r#"  policy_arn = "{policy}""#)?;
+            if aws_managed || miam.is_excluded(short_policy_name) {
+                writeln!(writer, "  policy_arn = {}", hcl_string(policy))?;
             } else {
                 writeln!(
                     writer,
@@ -169,22 +242,27 @@ where
             }
             writeln!(writer, "}}")?;
 
-            writeln!(writer, "import {{")?;
-            writeln!(
-                writer,
-                "  to = aws_iam_group_policy_attachment.{}-{}",
-                group.name, short_policy_name
-            )?;
-            writeln!(writer, r#"  id = "{}/{}""#, group.name, policy)?;
-            writeln!(writer, "}}")?;
+            imports.push(Import::new(
+                "aws_iam_group_policy_attachment",
+                &format!("{}-{}", group.name, short_policy_name),
+                format!("{}/{}", group.name, policy),
+            ));
         }
     }
 
-    for role in &miam.roles {
+    for role in sorted_by_name(&miam.roles, |r| &r.name) {
+        if let Some(service_name) = role.service_linked_service_name() {
+            print_service_linked_role(writer, &mut imports, role, service_name)?;
+            continue;
+        }
+
         writeln!(writer, r#"resource "aws_iam_role" "{}" {{"#, role.name)?;
-        writeln!(writer, r#"  name = "{}""#, role.name)?;
+        writeln!(writer, "  name = {}", hcl_string(&role.name))?;
         if let Some(ref path) = role.path {
-            writeln!(writer, r#"  path = "{}""#, path)?;
+            writeln!(writer, "  path = {}", hcl_string(path))?;
+        }
+        if let Some(ref description) = role.description {
+            writeln!(writer, "  description = {}", hcl_string(description))?;
         }
         if role.assume_role_policy_document.is_some() {
             writeln!(
@@ -196,38 +274,47 @@ where
         if let Some(duration) = role.max_session_duration {
             writeln!(writer, "  max_session_duration = {}", duration)?;
         }
+        if let Some(ref policy_arn) = role.permissions_boundary {
+            writeln!(
+                writer,
+                "  permissions_boundary = {}",
+                hcl_string(policy_arn)
+            )?;
+        }
+        if role.force_detach_policies {
+            writeln!(writer, "  force_detach_policies = true")?;
+        }
+        print_tags(writer, &role.tags)?;
         writeln!(writer, "}}")?;
 
-        writeln!(writer, "import {{")?;
-        writeln!(writer, "  to = aws_iam_role.{}", role.name)?;
-        writeln!(writer, r#"  id = "{}""#, role.name)?;
-        writeln!(writer, "}}")?;
+        imports.push(Import::new("aws_iam_role", &role.name, role.name.clone()));
 
         if let Some(ref policy) = role.assume_role_policy_document {
             print_policy_document(writer, &format!("assume-role-{}", role.name), policy)?;
         }
 
-        for profile in &role.instance_profiles {
+        for profile in sorted(&role.instance_profiles) {
             writeln!(
                 writer,
                 r#"resource "aws_iam_instance_profile" "{profile}" {{"#,
             )?;
-            writeln!(writer, r#"  name = "{profile}""#)?;
+            writeln!(writer, "  name = {}", hcl_string(profile))?;
             writeln!(writer, "  role = aws_iam_role.{}.name", role.name)?;
             writeln!(writer, "}}")?;
-            writeln!(writer, "import {{")?;
-            writeln!(writer, "  to = aws_iam_instance_profile.{profile}",)?;
-            writeln!(writer, r#"  id = "{profile}""#)?;
-            writeln!(writer, "}}")?;
+            imports.push(Import::new(
+                "aws_iam_instance_profile",
+                profile,
+                profile.clone(),
+            ));
         }
 
-        for policy in &role.policies {
+        for policy in sorted_by_name(&role.policies, |p| &p.name) {
             writeln!(
                 writer,
                 r#"resource "aws_iam_role_policy" "{}-{}" {{"#,
                 role.name, policy.name
             )?;
-            writeln!(writer, r#"  name = "{}""#, policy.name)?;
+            writeln!(writer, "  name = {}", hcl_string(&policy.name))?;
             writeln!(writer, "  role = aws_iam_role.{}.name", role.name)?;
             writeln!(
                 writer,
@@ -236,24 +323,24 @@ where
             )?;
             writeln!(writer, "}}")?;
 
-            writeln!(writer, "import {{")?;
-            writeln!(
-                writer,
-                "  to = aws_iam_role_policy.{}-{}",
-                role.name, policy.name
-            )?;
-            writeln!(writer, r#"  id = "{}:{}""#, role.name, policy.name)?;
-            writeln!(writer, "}}")?;
+            imports.push(Import::new(
+                "aws_iam_role_policy",
+                &format!("{}-{}", role.name, policy.name),
+                format!("{}:{}", role.name, policy.name),
+            ));
 
             print_policy_document(writer, &format!("{}-{}", role.name, policy.name), policy)?;
         }
-        for policy in &role.attached_managed_policies {
-            let short_policy_name = policy.rsplit_once('/').map(|(_, x)| x).unwrap_or_else(|| {
-                panic!(
-                    "Invalid attached_managed_policies {} found in {} role",
-                    policy, role.name
+        for policy in sorted(&role.attached_managed_policies) {
+            let short_policy_name = short_policy_name(policy).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Invalid attached_managed_policies {} found in {} role",
+                        policy, role.name
+                    ),
                 )
-            });
+            })?;
             writeln!(
                 writer,
                 r#"resource "aws_iam_role_policy_attachment" "{}-{}" {{"#,
@@ -261,8 +348,8 @@ where
             )?;
             writeln!(writer, "  role = aws_iam_role.{}.name", role.name)?;
             let aws_managed = policy.starts_with("arn:aws:iam::aws:policy/");
-            if aws_managed {
-                writeln!(writer, r#"  policy_arn = "{policy}""#)?;
+            if aws_managed || miam.is_excluded(short_policy_name) {
+                writeln!(writer, "  policy_arn = {}", hcl_string(policy))?;
             } else {
                 writeln!(
                     writer,
@@ -271,28 +358,29 @@ where
             }
             writeln!(writer, "}}")?;
 
-            writeln!(writer, "import {{")?;
-            writeln!(
-                writer,
-                "  to = aws_iam_role_policy_attachment.{}-{}",
-                role.name, short_policy_name
-            )?;
-            writeln!(writer, r#"  id = "{}/{}""#, role.name, policy)?;
-            writeln!(writer, "}}")?;
+            imports.push(Import::new(
+                "aws_iam_role_policy_attachment",
+                &format!("{}-{}", role.name, short_policy_name),
+                format!("{}/{}", role.name, policy),
+            ));
         }
     }
 
-    for policy in &miam.managed_policies {
+    for policy in sorted_by_name(&miam.managed_policies, |p| &p.name) {
         writeln!(writer, r#"resource "aws_iam_policy" "{}" {{"#, policy.name)?;
-        writeln!(writer, r#"  name = "{}""#, policy.name)?;
+        writeln!(writer, "  name = {}", hcl_string(&policy.name))?;
         if let Some(ref path) = policy.path {
-            writeln!(writer, r#"  path = "{}""#, path)?;
+            writeln!(writer, "  path = {}", hcl_string(path))?;
+        }
+        if let Some(ref description) = policy.description {
+            writeln!(writer, "  description = {}", hcl_string(description))?;
         }
         writeln!(
             writer,
             "  policy = data.aws_iam_policy_document.{}.json",
             policy.name
         )?;
+        print_tags(writer, &policy.tags)?;
         writeln!(writer, "}}")?;
 
         let arn = match policy.path {
@@ -304,16 +392,173 @@ where
             .replace("//", "/"),
             None => format!("arn:aws:iam::<AWS_ACCOUNT_ID>:policy/{}", policy.name),
         };
+        imports.push(Import::new("aws_iam_policy", &policy.name, arn));
+
+        print_policy_document(writer, &policy.name, &policy.policy_document)?;
+    }
+    Ok(imports)
+}
+
+/// Resources are printed in the order of names so that the output doesn't depend on the order of
+/// definitions in IAMfile
+fn sorted_by_name<'a, T, F>(items: &'a [T], name: F) -> Vec<&'a T>
+where
+    F: Fn(&'a T) -> &'a str,
+{
+    let mut items: Vec<_> = items.iter().collect();
+    items.sort_by(|a, b| name(a).cmp(name(b)));
+    items
+}
+
+fn sorted(names: &[String]) -> Vec<&String> {
+    sorted_by_name(names, String::as_str)
+}
+
+/// Service-linked roles can't be created by aws_iam_role and their policies are managed by AWS
+fn print_service_linked_role<W>(
+    writer: &mut W,
+    imports: &mut Vec<Import>,
+    role: &crate::Role,
+    service_name: &str,
+) -> Result<(), std::io::Error>
+where
+    W: std::io::Write,
+{
+    writeln!(
+        writer,
+        r#"resource "aws_iam_service_linked_role" "{}" {{"#,
+        role.name
+    )?;
+    writeln!(writer, "  aws_service_name = {}", hcl_string(service_name))?;
+    if let Some(ref suffix) = role.custom_suffix {
+        writeln!(writer, "  custom_suffix = {}", hcl_string(suffix))?;
+    }
+    if let Some(ref description) = role.description {
+        writeln!(writer, "  description = {}", hcl_string(description))?;
+    }
+    print_tags(writer, &role.tags)?;
+    writeln!(writer, "}}")?;
+
+    imports.push(Import::new(
+        "aws_iam_service_linked_role",
+        &role.name,
+        format!(
+            "arn:aws:iam::<AWS_ACCOUNT_ID>:role/aws-service-role/{}/{}",
+            service_name, role.name
+        ),
+    ));
+    Ok(())
+}
+
+fn print_tags<W>(
+    writer: &mut W,
+    tags: &std::collections::BTreeMap<String, String>,
+) -> Result<(), std::io::Error>
+where
+    W: std::io::Write,
+{
+    if !tags.is_empty() {
+        writeln!(writer, "  tags = {{")?;
+        for (key, value) in tags {
+            writeln!(writer, "    {} = {}", hcl_string(key), hcl_string(value))?;
+        }
+        writeln!(writer, "  }}")?;
+    }
+    Ok(())
+}
+
+/// Print `import {}` blocks and `moved {}` blocks for resources whose addresses differ from
+/// `options.previous_addresses`
+pub fn print_imports<W>(
+    writer: &mut W,
+    imports: &[Import],
+    options: &Options,
+) -> Result<(), std::io::Error>
+where
+    W: std::io::Write,
+{
+    let module = options.module.as_deref();
+    for import in imports {
         writeln!(writer, "import {{")?;
-        writeln!(writer, "  to = aws_iam_policy.{}", policy.name)?;
-        writeln!(writer, r#"  id = "{arn}""#,)?;
+        writeln!(writer, "  to = {}", import.address(module))?;
+        writeln!(writer, "  id = {}", hcl_string(&import.id))?;
         writeln!(writer, "}}")?;
+    }
 
-        print_policy_document(writer, &policy.name, &policy.policy_document)?;
+    let previous_addresses: std::collections::HashMap<_, _> = options
+        .previous_addresses
+        .iter()
+        .filter_map(|previous| {
+            previous
+                .resource_type()
+                .map(|resource_type| ((resource_type, previous.id.as_str()), &previous.address))
+        })
+        .collect();
+    for import in imports {
+        let address = import.address(module);
+        if let Some(previous_address) =
+            previous_addresses.get(&(import.resource_type.as_str(), import.id.as_str()))
+        {
+            if **previous_address != address {
+                writeln!(writer, "moved {{")?;
+                writeln!(writer, "  from = {}", previous_address)?;
+                writeln!(writer, "  to   = {}", address)?;
+                writeln!(writer, "}}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write the address mapping file, which is read by [`read_addresses`] in the next generation
+pub fn write_addresses<W>(
+    writer: &mut W,
+    imports: &[Import],
+    options: &Options,
+) -> Result<(), std::io::Error>
+where
+    W: std::io::Write,
+{
+    for import in imports {
+        writeln!(
+            writer,
+            "{}\t{}",
+            import.address(options.module.as_deref()),
+            import.id
+        )?;
     }
     Ok(())
 }
 
+/// Read the address mapping file written by [`write_addresses`]
+pub fn read_addresses<R>(reader: R) -> Result<Vec<PreviousAddress>, std::io::Error>
+where
+    R: std::io::BufRead,
+{
+    let mut addresses = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let (address, id) = line.split_once('\t').ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid address mapping at line {}: {}", i + 1, line),
+            )
+        })?;
+        addresses.push(PreviousAddress {
+            address: address.to_owned(),
+            id: id.to_owned(),
+        });
+    }
+    Ok(addresses)
+}
+
+pub(crate) fn short_policy_name(policy_arn: &str) -> Option<&str> {
+    policy_arn.rsplit_once('/').map(|(_, x)| x)
+}
+
 fn print_policy_document<W>(
     writer: &mut W,
     name: &str,
@@ -324,81 +569,99 @@ where
 {
     writeln!(writer, r#"data "aws_iam_policy_document" "{}" {{"#, name)?;
     if let Some(ref version) = policy_document.version {
-        writeln!(writer, r#"  version = "{}""#, version)?;
+        writeln!(writer, "  version = {}", hcl_string(version))?;
     }
     for statement in &policy_document.statements {
         writeln!(writer, r#"  statement {{"#)?;
         if let Some(ref sid) = statement.sid {
-            writeln!(writer, r#"    sid = "{sid}""#)?;
+            writeln!(writer, "    sid = {}", hcl_string(sid))?;
         }
-        writeln!(writer, r#"    effect = "{}""#, statement.effect)?;
-        writeln!(writer, "    actions = {:?}", statement.actions)?;
+        writeln!(writer, "    effect = {}", hcl_string(&statement.effect))?;
+        writeln!(writer, "    actions = {}", hcl_strings(&statement.actions))?;
 
         writeln!(
             writer,
-            "    resources = {:?}",
-            statement
-                .resources
-                .iter()
-                .map(|s| replace_iam_interpolation(s))
-                .collect::<Vec<_>>(),
+            "    resources = {}",
+            hcl_strings(
+                &statement
+                    .resources
+                    .iter()
+                    .map(|s| replace_iam_interpolation(s))
+                    .collect::<Vec<_>>()
+            ),
         )?;
         for condition in &statement.conditions {
             writeln!(writer, "      condition {{")?;
-            writeln!(writer, r#"      test = "{}""#, condition.test)?;
-            writeln!(writer, r#"      variable = "{}""#, condition.variable)?;
+            writeln!(writer, "      test = {}", hcl_string(&condition.test))?;
             writeln!(
                 writer,
-                "      values = {:?}",
-                condition
-                    .values
-                    .iter()
-                    .map(|s| replace_iam_interpolation(s))
-                    .collect::<Vec<_>>()
+                "      variable = {}",
+                hcl_string(&condition.variable)
+            )?;
+            writeln!(
+                writer,
+                "      values = {}",
+                hcl_strings(
+                    &condition
+                        .values
+                        .iter()
+                        .map(|s| replace_iam_interpolation(s))
+                        .collect::<Vec<_>>()
+                ),
             )?;
             writeln!(writer, "      }}")?;
         }
 
         for principal in &statement.principals {
             writeln!(writer, "      principals {{")?;
-            writeln!(writer, r#"      type  = "{}""#, principal.typ)?;
+            writeln!(writer, "      type  = {}", hcl_string(&principal.typ))?;
             writeln!(
                 writer,
-                "      identifiers = {:?}",
-                principal
-                    .identifiers
-                    .iter()
-                    .map(|s| replace_iam_interpolation(s))
-                    .collect::<Vec<_>>()
+                "      identifiers = {}",
+                hcl_strings(
+                    &principal
+                        .identifiers
+                        .iter()
+                        .map(|s| replace_iam_interpolation(s))
+                        .collect::<Vec<_>>()
+                ),
             )?;
             writeln!(writer, "      }}")?;
         }
 
         if !statement.not_actions.is_empty() {
-            writeln!(writer, "    not_actions = {:?}", statement.not_actions)?;
+            writeln!(
+                writer,
+                "    not_actions = {}",
+                hcl_strings(&statement.not_actions)
+            )?;
         }
         if !statement.not_resources.is_empty() {
             writeln!(
                 writer,
-                "    not_resources = {:?}",
-                statement
-                    .resources
-                    .iter()
-                    .map(|s| replace_iam_interpolation(s))
-                    .collect::<Vec<_>>(),
+                "    not_resources = {}",
+                hcl_strings(
+                    &statement
+                        .not_resources
+                        .iter()
+                        .map(|s| replace_iam_interpolation(s))
+                        .collect::<Vec<_>>()
+                ),
             )?;
         }
         for principal in &statement.not_principals {
             writeln!(writer, "      not_principals {{")?;
-            writeln!(writer, r#"      type  = "{}""#, principal.typ)?;
+            writeln!(writer, "      type  = {}", hcl_string(&principal.typ))?;
             writeln!(
                 writer,
-                "      identifiers = {:?}",
-                principal
-                    .identifiers
-                    .iter()
-                    .map(|s| replace_iam_interpolation(s))
-                    .collect::<Vec<_>>()
+                "      identifiers = {}",
+                hcl_strings(
+                    &principal
+                        .identifiers
+                        .iter()
+                        .map(|s| replace_iam_interpolation(s))
+                        .collect::<Vec<_>>()
+                ),
             )?;
             writeln!(writer, "      }}")?;
         }
@@ -408,7 +671,185 @@ where
     writeln!(writer, "}}")
 }
 
+/// Quote `s` as an HCL string literal. `${` and `%{` are escaped so that they aren't interpreted as
+/// template sequences
+fn hcl_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '$' | '%' if chars.peek() == Some(&'{') => {
+                quoted.push(c);
+                quoted.push(c);
+            }
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// HCL list of string literals
+fn hcl_strings<S>(strings: &[S]) -> String
+where
+    S: AsRef<str>,
+{
+    let strings: Vec<_> = strings.iter().map(|s| hcl_string(s.as_ref())).collect();
+    format!("[{}]", strings.join(", "))
+}
+
 fn replace_iam_interpolation(s: &str) -> String {
     // https://registry.terraform.io/providers/hashicorp/aws/latest/docs/data-sources/iam_policy_document#context-variable-interpolation
     s.replace("${", "&{")
 }
+
+#[cfg(test)]
+mod test {
+    /// Convert `test/*/IAMfile` and compare the output with `expected.tf` in the same directory.
+    /// Set `UPDATE_SNAPSHOTS=1` to overwrite `expected.tf` with the current output.
+    #[test]
+    fn snapshots() {
+        let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+        let mut dirs: Vec<_> = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/test"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.join("IAMfile").is_file())
+            .collect();
+        dirs.sort();
+        assert!(!dirs.is_empty());
+
+        let mut failures = Vec::new();
+        for dir in dirs {
+            let miam = crate::loader::load_miam(dir.join("IAMfile"), Some(&dir))
+                .unwrap_or_else(|e| panic!("{}: {}", dir.display(), e));
+            crate::validate::validate(&miam).unwrap_or_else(|e| panic!("{}: {}", dir.display(), e));
+            let mut buf = Vec::new();
+            super::print_as_hcl2(&mut buf, &miam).unwrap();
+            let actual = String::from_utf8(buf).unwrap();
+
+            let expected_path = dir.join("expected.tf");
+            if update {
+                std::fs::write(&expected_path, &actual).unwrap();
+            } else if std::fs::read_to_string(&expected_path).ok().as_deref() != Some(&actual) {
+                failures.push(format!(
+                    "{} doesn't match:\n{}",
+                    expected_path.display(),
+                    actual
+                ));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn print_moved_blocks_for_changed_addresses() {
+        let imports = vec![
+            super::Import::new("aws_iam_user", "alice", "alice".to_owned()),
+            super::Import::new("aws_iam_group", "admin", "admin".to_owned()),
+        ];
+        let previous_addresses = super::read_addresses(
+            "aws_iam_user.alice\talice\nmodule.iam.aws_iam_group.admin\tadmin\n".as_bytes(),
+        )
+        .unwrap();
+        let options = super::Options {
+            module: Some("module.iam".to_owned()),
+            previous_addresses,
+        };
+
+        let mut buf = Vec::new();
+        super::print_imports(&mut buf, &imports, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"import {
+  to = module.iam.aws_iam_user.alice
+  id = "alice"
+}
+import {
+  to = module.iam.aws_iam_group.admin
+  id = "admin"
+}
+moved {
+  from = aws_iam_user.alice
+  to   = module.iam.aws_iam_user.alice
+}
+"#
+        );
+
+        let mut buf = Vec::new();
+        super::write_addresses(&mut buf, &imports, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "module.iam.aws_iam_user.alice\talice\nmodule.iam.aws_iam_group.admin\tadmin\n"
+        );
+    }
+
+    #[test]
+    fn escape_strings() {
+        assert_eq!(
+            super::hcl_string("say \"hi\"\\ ${name} %{if} $$ 100%\n\u{7}é"),
+            r#""say \"hi\"\\ $${name} %%{if} $$ 100%\n\u0007é""#
+        );
+        assert_eq!(super::hcl_strings::<&str>(&[]), "[]");
+        assert_eq!(super::hcl_strings(&["a", "b"]), r#"["a", "b"]"#);
+    }
+
+    #[test]
+    fn print_service_linked_role() {
+        let mut miam = crate::test::miam();
+        miam.roles.push(crate::Role {
+            path: Some("/aws-service-role/autoscaling.amazonaws.com/".to_owned()),
+            description: Some("Used by \"batch\" ${env}".to_owned()),
+            tags: [("team".to_owned(), "a\nb".to_owned())].into(),
+            custom_suffix: Some("bar_baz".to_owned()),
+            ..crate::test::role("AWSServiceRoleForAutoScaling_bar_baz")
+        });
+        let mut buf = Vec::new();
+        super::print_resources(&mut buf, &miam).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"resource "aws_iam_service_linked_role" "AWSServiceRoleForAutoScaling_bar_baz" {
+  aws_service_name = "autoscaling.amazonaws.com"
+  custom_suffix = "bar_baz"
+  description = "Used by \"batch\" $${env}"
+  tags = {
+    "team" = "a\nb"
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn print_not_resources() {
+        let policy = crate::test::policy(
+            "deny-others",
+            vec![crate::PolicyStatement {
+                not_actions: crate::test::strings(&["iam:ChangePassword"]),
+                not_resources: crate::test::strings(&["arn:aws:iam::*:user/${aws:username}"]),
+                ..crate::test::statement("Deny", &[], &[])
+            }],
+        );
+        let mut buf = Vec::new();
+        super::print_policy_document(&mut buf, "deny-others", &policy).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"data "aws_iam_policy_document" "deny-others" {
+  statement {
+    effect = "Deny"
+    actions = []
+    resources = []
+    not_actions = ["iam:ChangePassword"]
+    not_resources = ["arn:aws:iam::*:user/&{aws:username}"]
+  }
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,183 @@
+#[derive(Debug)]
+pub struct Problem {
+    pub location: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub problems: Vec<Problem>,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} problem(s) found in IAMfile", self.problems.len())?;
+        for problem in &self.problems {
+            write!(f, "\n  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for Error {}
+
+/// Check that the model can be converted to Terraform definitions
+pub fn validate(miam: &crate::Miam) -> Result<(), Error> {
+    let problems = problems(miam);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error { problems })
+    }
+}
+
+pub(crate) fn problems(miam: &crate::Miam) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for user in &miam.users {
+        let location = format!("user({})", user.user_name);
+        check_identifier(&mut problems, &location, &user.user_name);
+        check_policy_documents(&mut problems, &location, &user.policies);
+        for group in &user.groups {
+            check_identifier(&mut problems, &location, group);
+        }
+        check_attached_managed_policies(&mut problems, &location, &user.attached_managed_policies);
+    }
+    for group in &miam.groups {
+        let location = format!("group({})", group.name);
+        check_identifier(&mut problems, &location, &group.name);
+        check_policy_documents(&mut problems, &location, &group.policies);
+        check_attached_managed_policies(&mut problems, &location, &group.attached_managed_policies);
+    }
+    for role in &miam.roles {
+        let location = format!("role({})", role.name);
+        check_identifier(&mut problems, &location, &role.name);
+        check_policy_documents(&mut problems, &location, &role.policies);
+        for profile in &role.instance_profiles {
+            check_identifier(&mut problems, &location, profile);
+        }
+        check_attached_managed_policies(&mut problems, &location, &role.attached_managed_policies);
+    }
+    for policy in &miam.managed_policies {
+        let location = format!("managed_policy({})", policy.name);
+        check_identifier(&mut problems, &location, &policy.name);
+    }
+    problems
+}
+
+/// Names are used as Terraform resource names
+fn check_identifier(problems: &mut Vec<Problem>, location: &str, name: &str) {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && is_identifier_suffix(name);
+    if !valid {
+        problems.push(Problem {
+            location: location.to_owned(),
+            message: format!("{:?} cannot be used as Terraform resource name", name),
+        });
+    }
+}
+
+/// Inline policy names are used as suffixes of Terraform resource names
+fn check_identifier_suffix(problems: &mut Vec<Problem>, location: &str, name: &str) {
+    if name.is_empty() || !is_identifier_suffix(name) {
+        problems.push(Problem {
+            location: location.to_owned(),
+            message: format!("{:?} cannot be used in Terraform resource name", name),
+        });
+    }
+}
+
+fn is_identifier_suffix(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn check_policy_documents(
+    problems: &mut Vec<Problem>,
+    location: &str,
+    policies: &[crate::PolicyDocument],
+) {
+    for policy in policies {
+        check_identifier_suffix(problems, location, &policy.name);
+    }
+}
+
+fn check_attached_managed_policies(
+    problems: &mut Vec<Problem>,
+    location: &str,
+    policy_arns: &[String],
+) {
+    for policy_arn in policy_arns {
+        match crate::printer::short_policy_name(policy_arn) {
+            Some(short_policy_name) => {
+                check_identifier_suffix(problems, location, short_policy_name)
+            }
+            None => problems.push(Problem {
+                location: location.to_owned(),
+                message: format!("invalid attached_managed_policies {:?}", policy_arn),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{group, miam, policy, role, statement, strings, user};
+
+    #[test]
+    fn valid() {
+        let mut miam = miam();
+        miam.users.push(crate::User {
+            policies: vec![policy(
+                "s3-read_only",
+                vec![statement("Allow", &["s3:Get*"], &["*"])],
+            )],
+            groups: strings(&["admin"]),
+            attached_managed_policies: strings(&["arn:aws:iam::aws:policy/ReadOnlyAccess"]),
+            ..user("_alice")
+        });
+        miam.groups.push(group("admin"));
+        assert!(super::validate(&miam).is_ok());
+    }
+
+    #[test]
+    fn invalid_names() {
+        let mut miam = miam();
+        miam.users.push(crate::User {
+            groups: strings(&["1st-group"]),
+            ..user("alice.bob")
+        });
+        miam.groups.push(crate::Group {
+            policies: vec![policy("", Vec::new())],
+            ..group("admin")
+        });
+        miam.roles.push(crate::Role {
+            policies: vec![policy("s3:read", Vec::new())],
+            instance_profiles: strings(&["web profile"]),
+            attached_managed_policies: strings(&["ReadOnlyAccess"]),
+            ..role("web")
+        });
+        miam.managed_policies
+            .push(crate::test::managed_policy("-deny", Vec::new()));
+
+        let e = super::validate(&miam).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            r#"7 problem(s) found in IAMfile
+  user(alice.bob): "alice.bob" cannot be used as Terraform resource name
+  user(alice.bob): "1st-group" cannot be used as Terraform resource name
+  group(admin): "" cannot be used in Terraform resource name
+  role(web): "s3:read" cannot be used in Terraform resource name
+  role(web): "web profile" cannot be used as Terraform resource name
+  role(web): invalid attached_managed_policies "ReadOnlyAccess"
+  managed_policy(-deny): "-deny" cannot be used as Terraform resource name"#
+        );
+    }
+}
@@ -0,0 +1,454 @@
+/// Principal allowed to perform the action
+#[derive(Debug)]
+pub struct Grant<'a> {
+    /// e.g. `user(alice)`
+    pub principal: String,
+    pub allowed_by: Vec<Match<'a>>,
+    /// Deny statements with conditions, which may or may not apply
+    pub denied_by: Vec<Match<'a>>,
+    /// Attached policies whose documents are not defined in IAMfile, e.g. AWS managed policies
+    pub unknown_policies: Vec<&'a str>,
+    /// Statements of the permissions boundary allowing the action
+    pub bounded_by: Vec<Match<'a>>,
+    /// Permissions boundary whose document is not defined in IAMfile
+    pub unknown_boundary: Option<&'a str>,
+}
+
+/// Statement matching the action and the resource
+#[derive(Debug)]
+pub struct Match<'a> {
+    /// e.g. `group(admin).policy(admin).statements[0]`
+    pub location: String,
+    /// Conditions are reported as is since they depend on the request context
+    pub conditions: &'a [crate::PolicyCondition],
+}
+
+impl std::fmt::Display for Grant<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.principal)?;
+        for m in &self.allowed_by {
+            write!(f, "\n  allowed by {}", m)?;
+        }
+        for m in &self.denied_by {
+            write!(f, "\n  denied by {}", m)?;
+        }
+        for policy_arn in &self.unknown_policies {
+            write!(
+                f,
+                "\n  may be allowed by {} (not defined in IAMfile)",
+                policy_arn
+            )?;
+        }
+        for m in &self.bounded_by {
+            write!(f, "\n  within permissions boundary {}", m)?;
+        }
+        if let Some(policy_arn) = self.unknown_boundary {
+            write!(
+                f,
+                "\n  may be limited by permissions boundary {} (not defined in IAMfile)",
+                policy_arn
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Match<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.location)?;
+        for (i, condition) in self.conditions.iter().enumerate() {
+            write!(
+                f,
+                "{} {} {} {:?}",
+                if i == 0 { " if" } else { " and" },
+                condition.test,
+                condition.variable,
+                condition.values
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// List users and roles granted `action` on `resource` by identity-based policies.
+/// Any resource is matched if `resource` is `None`.
+///
+/// Policy variables such as `${aws:username}` are treated as wildcards in Allow statements and
+/// conditions are not evaluated, so the result may contain principals that aren't actually
+/// allowed. Deny statements with conditions or policy variables are reported as they may apply.
+/// Principals denied unconditionally or not allowed by their permissions boundaries are not
+/// listed.
+pub fn who_can<'a>(miam: &'a crate::Miam, action: &str, resource: Option<&str>) -> Vec<Grant<'a>> {
+    let mut grants = Vec::new();
+    for user in &miam.users {
+        let mut evaluator = Evaluator::new(miam, action, resource);
+        let location = format!("user({})", user.user_name);
+        evaluator.policies(&location, &user.policies);
+        evaluator.attachments(&user.attached_managed_policies);
+        for group in &user.groups {
+            if let Some(group) = miam.groups.iter().find(|g| g.name == *group) {
+                let location = format!("group({})", group.name);
+                evaluator.policies(&location, &group.policies);
+                evaluator.attachments(&group.attached_managed_policies);
+            }
+        }
+        grants.extend(evaluator.finish(location, user.permissions_boundary.as_deref()));
+    }
+    for role in &miam.roles {
+        let mut evaluator = Evaluator::new(miam, action, resource);
+        let location = format!("role({})", role.name);
+        evaluator.policies(&location, &role.policies);
+        evaluator.attachments(&role.attached_managed_policies);
+        grants.extend(evaluator.finish(location, role.permissions_boundary.as_deref()));
+    }
+    grants
+}
+
+struct Evaluator<'a, 'q> {
+    miam: &'a crate::Miam,
+    action: &'q str,
+    resource: Option<&'q str>,
+    allowed_by: Vec<Match<'a>>,
+    denied_by: Vec<Match<'a>>,
+    unknown_policies: Vec<&'a str>,
+    denied: bool,
+}
+
+impl<'a, 'q> Evaluator<'a, 'q> {
+    fn new(miam: &'a crate::Miam, action: &'q str, resource: Option<&'q str>) -> Self {
+        Self {
+            miam,
+            action,
+            resource,
+            allowed_by: Vec::new(),
+            denied_by: Vec::new(),
+            unknown_policies: Vec::new(),
+            denied: false,
+        }
+    }
+
+    fn policies(&mut self, location: &str, policies: &'a [crate::PolicyDocument]) {
+        for policy in policies {
+            self.policy(&format!("{}.policy({})", location, policy.name), policy);
+        }
+    }
+
+    fn attachments(&mut self, policy_arns: &'a [String]) {
+        for policy_arn in policy_arns {
+            self.attachment(policy_arn);
+        }
+    }
+
+    fn attachment(&mut self, policy_arn: &'a str) {
+        match self.miam.find_managed_policy(policy_arn) {
+            Some(policy) => self.policy(
+                &format!("managed_policy({})", policy.name),
+                &policy.policy_document,
+            ),
+            None => self.unknown_policies.push(policy_arn),
+        }
+    }
+
+    fn policy(&mut self, location: &str, policy: &'a crate::PolicyDocument) {
+        for (i, statement) in policy.statements.iter().enumerate() {
+            if !matches_action(statement, self.action) {
+                continue;
+            }
+            let m = Match {
+                location: format!("{}.statements[{}]", location, i),
+                conditions: &statement.conditions,
+            };
+            if statement.effect == "Deny" {
+                // Deny without the resource applies only when it covers all resources
+                let resource = self.resource.unwrap_or("*");
+                let exact = self.resource.is_none();
+                if statement.conditions.is_empty()
+                    && matches_resource(statement, resource, exact, Variables::NeverApply)
+                {
+                    self.denied = true;
+                } else if matches_resource(statement, resource, exact, Variables::MayApply) {
+                    self.denied_by.push(m);
+                }
+            } else if statement.effect == "Allow"
+                && self.resource.is_none_or(|resource| {
+                    matches_resource(statement, resource, false, Variables::MayApply)
+                })
+            {
+                self.allowed_by.push(m);
+            }
+        }
+    }
+
+    fn is_allowed(&self) -> bool {
+        !self.denied && (!self.allowed_by.is_empty() || !self.unknown_policies.is_empty())
+    }
+
+    /// The permissions boundary is evaluated like another principal, and the action must be
+    /// allowed by both
+    fn finish(self, principal: String, permissions_boundary: Option<&'a str>) -> Option<Grant<'a>> {
+        if !self.is_allowed() {
+            return None;
+        }
+        let mut grant = Grant {
+            principal,
+            allowed_by: self.allowed_by,
+            denied_by: self.denied_by,
+            unknown_policies: self.unknown_policies,
+            bounded_by: Vec::new(),
+            unknown_boundary: None,
+        };
+        if let Some(policy_arn) = permissions_boundary {
+            let mut boundary = Evaluator::new(self.miam, self.action, self.resource);
+            boundary.attachment(policy_arn);
+            if !boundary.is_allowed() {
+                return None;
+            }
+            grant.bounded_by = boundary.allowed_by;
+            grant.denied_by.extend(boundary.denied_by);
+            grant.unknown_boundary = boundary.unknown_policies.pop();
+        }
+        Some(grant)
+    }
+}
+
+fn matches_action(statement: &crate::PolicyStatement, action: &str) -> bool {
+    // Action names are case-insensitive
+    let action = action.to_ascii_lowercase();
+    let matches = |pattern: &String| wildcard_match(&pattern.to_ascii_lowercase(), &action);
+    if !statement.not_actions.is_empty() {
+        !statement.not_actions.iter().any(matches)
+    } else {
+        statement.actions.iter().any(matches)
+    }
+}
+
+/// How resource patterns with policy variables are matched, since the values of the variables
+/// depend on the request context
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variables {
+    /// The statement applies if it does for some values of the variables
+    MayApply,
+    /// The statement applies only if it does for any values of the variables
+    NeverApply,
+}
+
+/// When `exact` is true, `resource` itself is a pattern and must be covered entirely
+fn matches_resource(
+    statement: &crate::PolicyStatement,
+    resource: &str,
+    exact: bool,
+    variables: Variables,
+) -> bool {
+    // `wildcard` tells whether patterns with variables match anything or nothing
+    let matches = |wildcard: bool| {
+        move |pattern: &String| {
+            if pattern.contains("${") && !wildcard {
+                return false;
+            }
+            let pattern = replace_policy_variables(pattern);
+            if exact {
+                pattern == "*" || pattern == resource
+            } else {
+                wildcard_match(&pattern, resource)
+            }
+        }
+    };
+    let may_apply = variables == Variables::MayApply;
+    if !statement.not_resources.is_empty() {
+        !exact && !statement.not_resources.iter().any(matches(!may_apply))
+    } else if statement.resources.is_empty() {
+        true
+    } else {
+        statement.resources.iter().any(matches(may_apply))
+    }
+}
+
+fn replace_policy_variables(pattern: &str) -> std::borrow::Cow<'_, str> {
+    let Some(start) = pattern.find("${") else {
+        return std::borrow::Cow::Borrowed(pattern);
+    };
+    let mut replaced = pattern[..start].to_owned();
+    let mut rest = &pattern[start..];
+    while let Some(start) = rest.find("${") {
+        replaced.push_str(&rest[..start]);
+        match rest[start..].find('}') {
+            Some(end) => {
+                replaced.push('*');
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                replaced.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    replaced.push_str(rest);
+    std::borrow::Cow::Owned(replaced)
+}
+
+/// `*` matches any sequence of characters and `?` matches any single character
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star_p, star_v)) = backtrack {
+            p = star_p + 1;
+            v = star_v + 1;
+            backtrack = Some((star_p, star_v + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{group, managed_policy, miam, policy, role, statement, strings, user};
+
+    fn grants(miam: &crate::Miam, action: &str, resource: Option<&str>) -> Vec<String> {
+        super::who_can(miam, action, resource)
+            .iter()
+            .map(|grant| grant.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn wildcard_match() {
+        assert!(super::wildcard_match("s3:*", "s3:getobject"));
+        assert!(super::wildcard_match("s3:get*object", "s3:getobject"));
+        assert!(super::wildcard_match("s3:?etobject", "s3:getobject"));
+        assert!(!super::wildcard_match("s3:list*", "s3:getobject"));
+        assert!(!super::wildcard_match("s3:get", "s3:getobject"));
+        assert!(super::wildcard_match(
+            &super::replace_policy_variables("arn:aws:s3:::home/${aws:username}/*"),
+            "arn:aws:s3:::home/alice/file"
+        ));
+    }
+
+    #[test]
+    fn explicit_deny_overrides_allow() {
+        let mut miam = miam();
+        miam.users.push(crate::User {
+            policies: vec![policy(
+                "deny",
+                vec![statement("Deny", &["s3:*"], &["arn:aws:s3:::secret/*"])],
+            )],
+            groups: strings(&["readers"]),
+            ..user("alice")
+        });
+        miam.groups.push(crate::Group {
+            policies: vec![policy(
+                "allow",
+                vec![statement("Allow", &["S3:Get*"], &["*"])],
+            )],
+            attached_managed_policies: strings(&["arn:aws:iam::aws:policy/ReadOnlyAccess"]),
+            ..group("readers")
+        });
+
+        assert_eq!(
+            grants(&miam, "s3:GetObject", Some("arn:aws:s3:::public/a")),
+            vec!["user(alice)\n  allowed by group(readers).policy(allow).statements[0]\n  may be allowed by arn:aws:iam::aws:policy/ReadOnlyAccess (not defined in IAMfile)"]
+        );
+        assert!(grants(&miam, "s3:GetObject", Some("arn:aws:s3:::secret/a")).is_empty());
+        // The deny doesn't cover all resources
+        assert_eq!(grants(&miam, "s3:GetObject", None).len(), 1);
+    }
+
+    #[test]
+    fn policy_variables() {
+        let mut miam = miam();
+        miam.users.push(crate::User {
+            policies: vec![policy(
+                "home",
+                vec![
+                    statement(
+                        "Allow",
+                        &["s3:GetObject"],
+                        &["arn:aws:s3:::home/${aws:username}/*"],
+                    ),
+                    statement(
+                        "Deny",
+                        &["s3:GetObject"],
+                        &["arn:aws:s3:::home/${aws:username}/secret"],
+                    ),
+                ],
+            )],
+            ..user("alice")
+        });
+        miam.roles.push(crate::Role {
+            policies: vec![policy(
+                "home",
+                vec![
+                    statement("Allow", &["s3:GetObject"], &["*"]),
+                    crate::PolicyStatement {
+                        not_resources: strings(&["arn:aws:s3:::home/${aws:userid}/*"]),
+                        ..statement("Deny", &["s3:GetObject"], &[])
+                    },
+                ],
+            )],
+            ..role("web")
+        });
+
+        // Variables in Deny statements don't deny unconditionally
+        assert_eq!(
+            grants(&miam, "s3:GetObject", Some("arn:aws:s3:::home/alice/secret")),
+            vec![
+                "user(alice)\n  allowed by user(alice).policy(home).statements[0]\n  denied by user(alice).policy(home).statements[1]",
+                "role(web)\n  allowed by role(web).policy(home).statements[0]\n  denied by role(web).policy(home).statements[1]",
+            ]
+        );
+        assert_eq!(
+            grants(&miam, "s3:GetObject", Some("arn:aws:s3:::home/alice/file")),
+            vec![
+                "user(alice)\n  allowed by user(alice).policy(home).statements[0]",
+                "role(web)\n  allowed by role(web).policy(home).statements[0]\n  denied by role(web).policy(home).statements[1]",
+            ]
+        );
+    }
+
+    #[test]
+    fn permissions_boundary() {
+        let mut miam = miam();
+        miam.managed_policies.push(managed_policy(
+            "boundary",
+            vec![
+                statement("Allow", &["s3:*"], &["*"]),
+                statement("Deny", &["s3:DeleteObject"], &["*"]),
+            ],
+        ));
+        let admin = || policy("admin", vec![statement("Allow", &["*"], &["*"])]);
+        miam.users.push(crate::User {
+            policies: vec![admin()],
+            permissions_boundary: Some("arn:aws:iam::123456789012:policy/boundary".to_owned()),
+            ..user("alice")
+        });
+        miam.roles.push(crate::Role {
+            policies: vec![admin()],
+            permissions_boundary: Some("arn:aws:iam::aws:policy/PowerUserAccess".to_owned()),
+            ..role("web")
+        });
+
+        assert_eq!(
+            grants(&miam, "s3:GetObject", None),
+            vec![
+                "user(alice)\n  allowed by user(alice).policy(admin).statements[0]\n  within permissions boundary managed_policy(boundary).statements[0]",
+                "role(web)\n  allowed by role(web).policy(admin).statements[0]\n  may be limited by permissions boundary arn:aws:iam::aws:policy/PowerUserAccess (not defined in IAMfile)",
+            ]
+        );
+        // Not allowed or denied by the boundary
+        assert_eq!(
+            grants(&miam, "ec2:RunInstances", None),
+            vec!["role(web)\n  allowed by role(web).policy(admin).statements[0]\n  may be limited by permissions boundary arn:aws:iam::aws:policy/PowerUserAccess (not defined in IAMfile)"]
+        );
+        assert_eq!(grants(&miam, "s3:DeleteObject", None).len(), 1);
+    }
+}
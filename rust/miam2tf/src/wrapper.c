@@ -27,3 +27,33 @@ mrb_value wrapper_mrb_nil_value(void) {
 }
 
 mrb_int wrapper_mrb_integer(mrb_value n) { return mrb_integer(n); }
+
+mrb_value wrapper_mrb_obj_value(struct RObject *p) { return mrb_obj_value(p); }
+
+mrb_int wrapper_mrb_string_p(mrb_value o) { return mrb_string_p(o); }
+
+mrb_int wrapper_mrb_array_p(mrb_value o) { return mrb_array_p(o); }
+
+mrb_int wrapper_mrb_integer_p(mrb_value o) { return mrb_integer_p(o); }
+
+mrb_value wrapper_mrb_bool_value(mrb_bool b) { return mrb_bool_value(b); }
+
+mrb_value wrapper_mrb_int_value(mrb_state *mrb, mrb_int i) { return mrb_int_value(mrb, i); }
+
+mrb_value wrapper_mrb_float_value(mrb_state *mrb, mrb_float f) { return mrb_float_value(mrb, f); }
+
+mrb_int wrapper_mrb_test(mrb_value o) { return mrb_test(o); }
+
+mrb_int wrapper_mrb_hash_p(mrb_value o) { return mrb_hash_p(o); }
+
+mrb_int wrapper_mrb_true_p(mrb_value o) { return mrb_true_p(o); }
+
+mrb_int wrapper_mrb_false_p(mrb_value o) { return mrb_false_p(o) && !mrb_nil_p(o); }
+
+const char* wrapper_rstring_ptr(mrb_value s) { return RSTRING_PTR(s); }
+
+mrb_int wrapper_rstring_len(mrb_value s) { return RSTRING_LEN(s); }
+
+int wrapper_mrb_gc_arena_save(mrb_state *mrb) { return mrb_gc_arena_save(mrb); }
+
+void wrapper_mrb_gc_arena_restore(mrb_state *mrb, int idx) { mrb_gc_arena_restore(mrb, idx); }
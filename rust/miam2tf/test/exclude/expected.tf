@@ -0,0 +1,48 @@
+resource "aws_iam_user" "alice" {
+  name = "alice"
+}
+resource "aws_iam_user_group_membership" "alice" {
+  user = aws_iam_user.alice.name
+  groups = [
+    aws_iam_group.developers.name,
+    "legacy-admins",
+  ]
+}
+resource "aws_iam_user_policy_attachment" "alice-legacy-deny" {
+  user = aws_iam_user.alice.name
+  policy_arn = "arn:aws:iam::123456789012:policy/legacy-deny"
+}
+resource "aws_iam_group" "developers" {
+  name = "developers"
+}
+resource "aws_iam_role" "web" {
+  name = "web"
+}
+resource "aws_iam_instance_profile" "web" {
+  name = "web"
+  role = aws_iam_role.web.name
+}
+import {
+  to = aws_iam_user.alice
+  id = "alice"
+}
+import {
+  to = aws_iam_user_group_membership.alice
+  id = "alice/developers/legacy-admins"
+}
+import {
+  to = aws_iam_user_policy_attachment.alice-legacy-deny
+  id = "alice/arn:aws:iam::123456789012:policy/legacy-deny"
+}
+import {
+  to = aws_iam_group.developers
+  id = "developers"
+}
+import {
+  to = aws_iam_role.web
+  id = "web"
+}
+import {
+  to = aws_iam_instance_profile.web
+  id = "web"
+}
@@ -0,0 +1,50 @@
+resource "aws_iam_role" "batch" {
+  name = "batch"
+  assume_role_policy = data.aws_iam_policy_document.assume-role-batch.json
+}
+data "aws_iam_policy_document" "assume-role-batch" {
+  version = "2012-10-17"
+  statement {
+    effect = "Allow"
+    actions = ["sts:AssumeRole"]
+    resources = []
+      principals {
+      type  = "Service"
+      identifiers = ["ecs-tasks.amazonaws.com"]
+      }
+  }
+}
+resource "aws_iam_role_policy" "batch-batch" {
+  name = "batch"
+  role = aws_iam_role.batch.name
+  policy = data.aws_iam_policy_document.batch-batch.json
+}
+data "aws_iam_policy_document" "batch-batch" {
+  version = "2012-10-17"
+  statement {
+    sid = "ReadConfig"
+    effect = "Allow"
+    actions = ["s3:GetObject", "s3:ListBucket"]
+    resources = ["arn:aws:s3:::batch-config", "arn:aws:s3:::batch-config/&{aws:PrincipalTag/team}/*"]
+  }
+  statement {
+    effect = "Deny"
+    actions = []
+    resources = []
+      condition {
+      test = "Bool"
+      variable = "aws:SecureTransport"
+      values = ["false"]
+      }
+    not_actions = ["s3:*"]
+    not_resources = ["arn:aws:s3:::batch-config/*"]
+  }
+}
+import {
+  to = aws_iam_role.batch
+  id = "batch"
+}
+import {
+  to = aws_iam_role_policy.batch-batch
+  id = "batch:batch"
+}
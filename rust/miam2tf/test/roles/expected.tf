@@ -0,0 +1,95 @@
+resource "aws_iam_role" "batch" {
+  name = "batch"
+  path = "/service-role/"
+  assume_role_policy = data.aws_iam_policy_document.assume-role-batch.json
+  max_session_duration = 7200
+}
+data "aws_iam_policy_document" "assume-role-batch" {
+  version = "2012-10-17"
+  statement {
+    effect = "Allow"
+    actions = ["sts:AssumeRole"]
+    resources = []
+      principals {
+      type  = "Service"
+      identifiers = ["ec2.amazonaws.com"]
+      }
+  }
+}
+resource "aws_iam_instance_profile" "batch" {
+  name = "batch"
+  role = aws_iam_role.batch.name
+}
+resource "aws_iam_role_policy" "batch-logs" {
+  name = "logs"
+  role = aws_iam_role.batch.name
+  policy = data.aws_iam_policy_document.batch-logs.json
+}
+data "aws_iam_policy_document" "batch-logs" {
+  version = "2012-10-17"
+  statement {
+    sid = "WriteLogs"
+    effect = "Allow"
+    actions = ["logs:CreateLogStream", "logs:PutLogEvents"]
+    resources = ["*"]
+      condition {
+      test = "StringEquals"
+      variable = "aws:RequestedRegion"
+      values = ["ap-northeast-1", "us-east-1"]
+      }
+      condition {
+      test = "Bool"
+      variable = "aws:SecureTransport"
+      values = ["true"]
+      }
+  }
+}
+resource "aws_iam_role_policy_attachment" "batch-AmazonEC2RoleforSSM" {
+  role = aws_iam_role.batch.name
+  policy_arn = "arn:aws:iam::aws:policy/service-role/AmazonEC2RoleforSSM"
+}
+resource "aws_iam_role" "deploy" {
+  name = "deploy"
+  assume_role_policy = data.aws_iam_policy_document.assume-role-deploy.json
+}
+data "aws_iam_policy_document" "assume-role-deploy" {
+  version = "2012-10-17"
+  statement {
+    effect = "Allow"
+    actions = ["sts:AssumeRole"]
+    resources = []
+      principals {
+      type  = "AWS"
+      identifiers = ["arn:aws:iam::123456789012:root"]
+      }
+  }
+  statement {
+    effect = "Deny"
+    actions = ["sts:AssumeRole"]
+    resources = []
+      not_principals {
+      type  = "AWS"
+      identifiers = ["arn:aws:iam::123456789012:role/deployer"]
+      }
+  }
+}
+import {
+  to = aws_iam_role.batch
+  id = "batch"
+}
+import {
+  to = aws_iam_instance_profile.batch
+  id = "batch"
+}
+import {
+  to = aws_iam_role_policy.batch-logs
+  id = "batch:logs"
+}
+import {
+  to = aws_iam_role_policy_attachment.batch-AmazonEC2RoleforSSM
+  id = "batch/arn:aws:iam::aws:policy/service-role/AmazonEC2RoleforSSM"
+}
+import {
+  to = aws_iam_role.deploy
+  id = "deploy"
+}
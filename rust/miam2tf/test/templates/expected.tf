@@ -0,0 +1,48 @@
+resource "aws_iam_user" "carol" {
+  name = "carol"
+}
+resource "aws_iam_user_policy" "carol-carol-parameters" {
+  name = "carol-parameters"
+  user = aws_iam_user.carol.name
+  policy = data.aws_iam_policy_document.carol-carol-parameters.json
+}
+data "aws_iam_policy_document" "carol-carol-parameters" {
+  version = "2012-10-17"
+  statement {
+    effect = "Allow"
+    actions = ["ssm:GetParameter"]
+    resources = ["arn:aws:ssm:ap-northeast-1:*:parameter/carol/*"]
+  }
+}
+resource "aws_iam_user" "dave" {
+  name = "dave"
+}
+resource "aws_iam_user_policy" "dave-dave-parameters" {
+  name = "dave-parameters"
+  user = aws_iam_user.dave.name
+  policy = data.aws_iam_policy_document.dave-dave-parameters.json
+}
+data "aws_iam_policy_document" "dave-dave-parameters" {
+  version = "2012-10-17"
+  statement {
+    effect = "Allow"
+    actions = ["ssm:GetParameter"]
+    resources = ["arn:aws:ssm:us-east-1:*:parameter/dave/*"]
+  }
+}
+import {
+  to = aws_iam_user.carol
+  id = "carol"
+}
+import {
+  to = aws_iam_user_policy.carol-carol-parameters
+  id = "carol:carol-parameters"
+}
+import {
+  to = aws_iam_user.dave
+  id = "dave"
+}
+import {
+  to = aws_iam_user_policy.dave-dave-parameters
+  id = "dave:dave-parameters"
+}
@@ -0,0 +1,124 @@
+resource "aws_iam_user" "alice" {
+  name = "alice"
+  tags = {
+    "team" = "infra"
+  }
+}
+resource "aws_iam_user_group_membership" "alice" {
+  user = aws_iam_user.alice.name
+  groups = [
+    aws_iam_group.viewers.name,
+  ]
+}
+resource "aws_iam_user" "bob" {
+  name = "bob"
+  path = "/developers/"
+}
+resource "aws_iam_user_policy" "bob-ssm" {
+  name = "ssm"
+  user = aws_iam_user.bob.name
+  policy = data.aws_iam_policy_document.bob-ssm.json
+}
+data "aws_iam_policy_document" "bob-ssm" {
+  version = "2012-10-17"
+  statement {
+    effect = "Allow"
+    actions = ["ssm:GetParameter", "ssm:GetParameters"]
+    resources = ["arn:aws:ssm:*:*:parameter/&{aws:username}/*"]
+  }
+}
+resource "aws_iam_user_group_membership" "bob" {
+  user = aws_iam_user.bob.name
+  groups = [
+    aws_iam_group.developers.name,
+    aws_iam_group.viewers.name,
+  ]
+}
+resource "aws_iam_user_policy_attachment" "bob-ReadOnlyAccess" {
+  user = aws_iam_user.bob.name
+  policy_arn = "arn:aws:iam::aws:policy/ReadOnlyAccess"
+}
+resource "aws_iam_group" "developers" {
+  name = "developers"
+  path = "/"
+}
+resource "aws_iam_group_policy" "developers-deny-iam" {
+  name = "deny-iam"
+  group = aws_iam_group.developers.name
+  policy = data.aws_iam_policy_document.developers-deny-iam.json
+}
+data "aws_iam_policy_document" "developers-deny-iam" {
+  version = "2012-10-17"
+  statement {
+    effect = "Deny"
+    actions = []
+    resources = []
+    not_actions = ["iam:Get*", "iam:List*"]
+    not_resources = ["arn:aws:iam::*:user/&{aws:username}"]
+  }
+}
+resource "aws_iam_group" "viewers" {
+  name = "viewers"
+}
+resource "aws_iam_group_policy_attachment" "viewers-s3-viewer" {
+  group = aws_iam_group.viewers.name
+  policy_arn = aws_iam_policy.s3-viewer.arn
+}
+resource "aws_iam_policy" "s3-viewer" {
+  name = "s3-viewer"
+  path = "/"
+  description = "Read S3 buckets"
+  policy = data.aws_iam_policy_document.s3-viewer.json
+}
+data "aws_iam_policy_document" "s3-viewer" {
+  version = "2012-10-17"
+  statement {
+    effect = "Allow"
+    actions = ["s3:Get*", "s3:List*"]
+    resources = ["*"]
+  }
+}
+import {
+  to = aws_iam_user.alice
+  id = "alice"
+}
+import {
+  to = aws_iam_user_group_membership.alice
+  id = "alice/viewers"
+}
+import {
+  to = aws_iam_user.bob
+  id = "bob"
+}
+import {
+  to = aws_iam_user_policy.bob-ssm
+  id = "bob:ssm"
+}
+import {
+  to = aws_iam_user_group_membership.bob
+  id = "bob/developers/viewers"
+}
+import {
+  to = aws_iam_user_policy_attachment.bob-ReadOnlyAccess
+  id = "bob/arn:aws:iam::aws:policy/ReadOnlyAccess"
+}
+import {
+  to = aws_iam_group.developers
+  id = "developers"
+}
+import {
+  to = aws_iam_group_policy.developers-deny-iam
+  id = "developers:deny-iam"
+}
+import {
+  to = aws_iam_group.viewers
+  id = "viewers"
+}
+import {
+  to = aws_iam_group_policy_attachment.viewers-s3-viewer
+  id = "viewers/arn:aws:iam::123456789012:policy/s3-viewer"
+}
+import {
+  to = aws_iam_policy.s3-viewer
+  id = "arn:aws:iam::<AWS_ACCOUNT_ID>:policy/s3-viewer"
+}
@@ -0,0 +1,280 @@
+//! Detect CNAME records which are dangling or likely to be taken over
+
+/// Suffixes of services where anyone can claim the name once the resource is deleted
+const TAKEOVER_SUFFIXES: &[(&str, &str)] = &[
+    ("elasticbeanstalk.com", "AWS Elastic Beanstalk"),
+    ("s3.amazonaws.com", "AWS S3"),
+    ("cloudfront.net", "AWS CloudFront"),
+    ("azurewebsites.net", "Azure App Service"),
+    ("cloudapp.net", "Azure Cloud Services"),
+    ("cloudapp.azure.com", "Azure Virtual Machines"),
+    ("trafficmanager.net", "Azure Traffic Manager"),
+    ("blob.core.windows.net", "Azure Blob Storage"),
+    ("azureedge.net", "Azure CDN"),
+    ("storage.googleapis.com", "Google Cloud Storage"),
+    ("github.io", "GitHub Pages"),
+    ("herokuapp.com", "Heroku"),
+    ("herokudns.com", "Heroku"),
+    ("netlify.app", "Netlify"),
+    ("vercel.app", "Vercel"),
+    ("myshopify.com", "Shopify"),
+    ("zendesk.com", "Zendesk"),
+    ("fastly.net", "Fastly"),
+];
+
+/// Chains longer than this are reported. Some resolvers give up following longer chains
+const LONG_CHAIN: usize = 8;
+
+#[derive(Debug, serde::Serialize)]
+pub struct CnameCheck {
+    pub name: String,
+    /// CNAME records followed from `name`
+    pub chain: Vec<crate::Record>,
+    pub findings: Vec<Finding>,
+}
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// The chain ends in NXDOMAIN
+    Dangling {
+        target: String,
+        provider: Option<&'static str>,
+    },
+    /// The chain ends in a provider's name without addresses
+    Unbacked {
+        target: String,
+        provider: &'static str,
+    },
+    Loop {
+        target: String,
+    },
+    TooLong {
+        length: usize,
+    },
+    /// The lookup failed other than NXDOMAIN, e.g. SERVFAIL of a lame delegation
+    Error {
+        target: String,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dangling {
+                target,
+                provider: Some(provider),
+            } => write!(
+                f,
+                "dangling CNAME to {} on {} (NXDOMAIN), possible takeover",
+                target, provider
+            ),
+            Self::Dangling {
+                target,
+                provider: None,
+            } => write!(f, "dangling CNAME to {} (NXDOMAIN)", target),
+            Self::Unbacked { target, provider } => write!(
+                f,
+                "CNAME to {} on {} has no addresses, possible takeover",
+                target, provider
+            ),
+            Self::Loop { target } => write!(f, "CNAME loop at {}", target),
+            Self::TooLong { length } => write!(f, "CNAME chain of {} records is too long", length),
+            Self::Error { target, message } => {
+                write!(f, "failed to look up {}: {}", target, message)
+            }
+        }
+    }
+}
+
+/// Follow the CNAME chain from `name` and report problems. Only DNS is examined, so resources
+/// which still resolve, e.g. deleted S3 buckets, are not detected
+pub async fn check_cname<P>(resolver: &hickory_resolver::Resolver<P>, name: &str) -> CnameCheck
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    let mut check = CnameCheck {
+        name: name.to_owned(),
+        chain: Vec::new(),
+        findings: Vec::new(),
+    };
+    follow_chain(resolver, &mut check).await;
+    if check.chain.len() > LONG_CHAIN {
+        check.findings.push(Finding::TooLong {
+            length: check.chain.len(),
+        });
+    }
+    check
+}
+
+async fn follow_chain<P>(resolver: &hickory_resolver::Resolver<P>, check: &mut CnameCheck)
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    use hickory_resolver::proto::ProtoErrorKind;
+    use hickory_resolver::proto::op::ResponseCode;
+    use hickory_resolver::proto::rr::RecordType;
+
+    let mut current = match hickory_resolver::Name::from_utf8(&check.name) {
+        Ok(mut name) => {
+            name.set_fqdn(true);
+            name
+        }
+        Err(e) => {
+            check.findings.push(Finding::Error {
+                target: check.name.clone(),
+                message: e.to_string(),
+            });
+            return;
+        }
+    };
+    let mut seen = std::collections::HashSet::new();
+    seen.insert(current.to_lowercase());
+    for _ in 0..crate::MAX_CNAME_CHAIN {
+        let e = match resolver.lookup(current.clone(), RecordType::CNAME).await {
+            Ok(lookup) => {
+                let Some((ttl, target)) = lookup.record_iter().find_map(|record| {
+                    record
+                        .data()
+                        .as_cname()
+                        .map(|c| (record.ttl(), c.0.clone()))
+                }) else {
+                    break;
+                };
+                check.chain.push(crate::Record {
+                    name: current.to_utf8(),
+                    ttl,
+                    data: crate::RecordData::Cname {
+                        target: target.to_utf8(),
+                    },
+                    proof: None,
+                });
+                if !seen.insert(target.to_lowercase()) {
+                    check.findings.push(Finding::Loop {
+                        target: target.to_utf8(),
+                    });
+                    return;
+                }
+                current = target;
+                continue;
+            }
+            Err(e) => e,
+        };
+        match e.proto().map(|e| e.kind()) {
+            Some(ProtoErrorKind::NoRecordsFound {
+                response_code: ResponseCode::NXDomain,
+                ..
+            }) => {
+                // The queried name itself not existing isn't a problem
+                if !check.chain.is_empty() {
+                    check.findings.push(Finding::Dangling {
+                        target: current.to_utf8(),
+                        provider: provider(&current),
+                    });
+                }
+                return;
+            }
+            Some(ProtoErrorKind::NoRecordsFound {
+                response_code: ResponseCode::NoError,
+                ..
+            }) => break,
+            _ => {
+                check.findings.push(Finding::Error {
+                    target: current.to_utf8(),
+                    message: e.to_string(),
+                });
+                return;
+            }
+        }
+    }
+    if check.chain.len() >= crate::MAX_CNAME_CHAIN {
+        // Not followed to the end, and reported as too long
+        return;
+    }
+
+    if !check.chain.is_empty()
+        && let Some(provider) = provider(&current)
+    {
+        let has_addresses = resolver
+            .lookup_ip(current.clone())
+            .await
+            .is_ok_and(|lookup| lookup.iter().next().is_some());
+        if !has_addresses {
+            check.findings.push(Finding::Unbacked {
+                target: current.to_utf8(),
+                provider,
+            });
+        }
+    }
+}
+
+fn provider(name: &hickory_resolver::Name) -> Option<&'static str> {
+    let name = name.to_lowercase().to_utf8();
+    let name = name.trim_end_matches('.');
+    TAKEOVER_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| {
+            name.strip_suffix(suffix)
+                .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.'))
+        })
+        .map(|(_, provider)| *provider)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn provider() {
+        let provider = |name| super::provider(&hickory_resolver::Name::from_ascii(name).unwrap());
+        assert_eq!(
+            provider("foo.azurewebsites.net."),
+            Some("Azure App Service")
+        );
+        assert_eq!(provider("Foo.GitHub.io"), Some("GitHub Pages"));
+        assert_eq!(provider("fooazurewebsites.net."), None);
+        assert_eq!(provider("example.com."), None);
+    }
+
+    #[tokio::test]
+    async fn dangling() {
+        use hickory_resolver::Name;
+        use hickory_resolver::proto::op::ResponseCode;
+        use hickory_resolver::proto::rr::{RData, Record, RecordType, rdata};
+
+        let addr = crate::stub::stub_server(([127, 0, 0, 1], 0).into(), |query, response| {
+            response.set_authoritative(true);
+            match query.name().to_ascii().as_str() {
+                "www.example." if query.query_type() == RecordType::CNAME => {
+                    response.add_answer(Record::from_rdata(
+                        query.name().clone(),
+                        300,
+                        RData::CNAME(rdata::CNAME(
+                            Name::from_ascii("gone.azurewebsites.net.").unwrap(),
+                        )),
+                    ));
+                }
+                "www.example." => {}
+                _ => {
+                    response.set_response_code(ResponseCode::NXDomain);
+                }
+            }
+        })
+        .await;
+        let resolver = crate::stub::resolver(addr);
+
+        let check = super::check_cname(&resolver, "www.example.").await;
+        assert_eq!(
+            check.findings,
+            vec![super::Finding::Dangling {
+                target: "gone.azurewebsites.net.".to_owned(),
+                provider: Some("Azure App Service"),
+            }]
+        );
+        assert!(
+            super::check_cname(&resolver, "missing.example.")
+                .await
+                .findings
+                .is_empty()
+        );
+    }
+}
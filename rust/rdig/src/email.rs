@@ -0,0 +1,614 @@
+//! Analyze records for email authentication: SPF, DMARC, DKIM and MTA-STS
+
+/// DKIM selectors can't be enumerated, so commonly used ones are tried
+const DKIM_SELECTORS: &[&str] = &[
+    "default",
+    "dkim",
+    "google",
+    "selector1",
+    "selector2",
+    "s1",
+    "s2",
+    "k1",
+    "k2",
+    "mail",
+    "smtp",
+    "mandrill",
+    "amazonses",
+];
+
+/// Maximum number of DNS-querying terms in SPF evaluation (RFC 7208 section 4.6.4)
+const SPF_LOOKUP_LIMIT: usize = 10;
+
+#[derive(Debug, serde::Serialize)]
+pub struct EmailReport {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spf: Option<Spf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dmarc: Option<String>,
+    /// Keys found for the known selectors
+    pub dkim: Vec<Dkim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mta_sts: Option<String>,
+    pub findings: Vec<Finding>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Spf {
+    pub record: String,
+    /// Number of DNS-querying terms including the ones in included records
+    pub lookups: usize,
+    /// Domains included directly or indirectly, and redirected to
+    pub includes: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Dkim {
+    pub selector: String,
+    pub record: String,
+}
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+        })
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+impl EmailReport {
+    fn push(&mut self, severity: Severity, message: String) {
+        self.findings.push(Finding { severity, message });
+    }
+}
+
+pub async fn check_email<P>(resolver: &hickory_resolver::Resolver<P>, name: &str) -> EmailReport
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    let name = name.trim_end_matches('.');
+    let mut report = EmailReport {
+        name: name.to_owned(),
+        spf: None,
+        dmarc: None,
+        dkim: Vec::new(),
+        mta_sts: None,
+        findings: Vec::new(),
+    };
+
+    let dkim_names: Vec<_> = DKIM_SELECTORS
+        .iter()
+        .map(|selector| format!("{}._domainkey.{}.", selector, name))
+        .collect();
+    let (spf_name, dmarc_name, mta_sts_name) = (
+        format!("{}.", name),
+        format!("_dmarc.{}.", name),
+        format!("_mta-sts.{}.", name),
+    );
+    let (spf, dmarc, mta_sts, dkim) = tokio::join!(
+        lookup_txt(resolver, &spf_name, "v=spf1"),
+        lookup_txt(resolver, &dmarc_name, "v=DMARC1"),
+        lookup_txt(resolver, &mta_sts_name, "v=STSv1"),
+        async {
+            let mut dkim = Vec::new();
+            for dkim_name in &dkim_names {
+                dkim.push(lookup_txt(resolver, dkim_name, "v=DKIM1").await);
+            }
+            dkim
+        },
+    );
+
+    match spf {
+        Ok(records) => check_spf(resolver, &mut report, records).await,
+        Err(e) => report.push(Severity::Error, format!("failed to look up SPF: {}", e)),
+    }
+    match dmarc {
+        Ok(records) => check_dmarc(&mut report, records),
+        Err(e) => report.push(Severity::Error, format!("failed to look up DMARC: {}", e)),
+    }
+    for (selector, records) in DKIM_SELECTORS.iter().zip(dkim) {
+        match records {
+            Ok(records) => {
+                for record in records {
+                    check_dkim(&mut report, selector, &record);
+                    report.dkim.push(Dkim {
+                        selector: selector.to_string(),
+                        record,
+                    });
+                }
+            }
+            Err(e) => report.push(
+                Severity::Warning,
+                format!("failed to look up DKIM selector {}: {}", selector, e),
+            ),
+        }
+    }
+    if report.dkim.is_empty() {
+        report.push(
+            Severity::Info,
+            format!(
+                "no DKIM keys found for known selectors ({})",
+                DKIM_SELECTORS.join(", ")
+            ),
+        );
+    }
+    match mta_sts {
+        Ok(mut records) => {
+            if records.len() > 1 {
+                report.push(
+                    Severity::Error,
+                    "multiple MTA-STS records are published".to_owned(),
+                );
+            }
+            if let Some(record) = records.pop() {
+                if !parse_tags(&record).iter().any(|(key, _)| *key == "id") {
+                    report.push(Severity::Error, "MTA-STS record has no id".to_owned());
+                }
+                report.push(
+                    Severity::Info,
+                    format!(
+                        "MTA-STS policy at https://mta-sts.{}/.well-known/mta-sts.txt is not verified",
+                        name
+                    ),
+                );
+                report.mta_sts = Some(record);
+            }
+        }
+        Err(e) => report.push(
+            Severity::Warning,
+            format!("failed to look up MTA-STS: {}", e),
+        ),
+    }
+    report.findings.sort_by_key(|finding| finding.severity);
+    report
+}
+
+/// TXT records starting with `version`, e.g. `v=spf1`
+async fn lookup_txt<P>(
+    resolver: &hickory_resolver::Resolver<P>,
+    name: &str,
+    version: &str,
+) -> Result<Vec<String>, String>
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    let records = crate::lookup_records(resolver, name, crate::QueryType::Txt).await?;
+    Ok(records
+        .into_iter()
+        .filter_map(|record| match record.data {
+            crate::RecordData::Txt { text } => Some(text),
+            _ => None,
+        })
+        .filter(|text| {
+            text.split([' ', ';'])
+                .next()
+                .is_some_and(|v| v.eq_ignore_ascii_case(version))
+        })
+        .collect())
+}
+
+async fn check_spf<P>(
+    resolver: &hickory_resolver::Resolver<P>,
+    report: &mut EmailReport,
+    mut records: Vec<String>,
+) where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    if records.len() > 1 {
+        report.push(
+            Severity::Error,
+            "multiple SPF records are published, which results in permerror".to_owned(),
+        );
+    }
+    let Some(record) = records.pop() else {
+        report.push(Severity::Error, "no SPF record".to_owned());
+        return;
+    };
+
+    let terms = parse_spf(&record);
+    let all = terms.iter().find(|term| term.mechanism == "all");
+    match all {
+        Some(term) if term.qualifier == '+' => report.push(
+            Severity::Error,
+            "+all allows any host to send mail".to_owned(),
+        ),
+        Some(term) if term.qualifier == '?' => {
+            report.push(Severity::Warning, "?all is neutral".to_owned())
+        }
+        Some(_) => {}
+        None if terms.iter().any(|term| term.mechanism == "redirect") => {}
+        None => report.push(
+            Severity::Warning,
+            "no all mechanism, so unmatched hosts are neutral".to_owned(),
+        ),
+    }
+
+    let mut spf = Spf {
+        record: record.clone(),
+        lookups: 0,
+        includes: Vec::new(),
+    };
+    // Included records are evaluated each time they are included, so they are counted toward the
+    // limit as many times. Records are looked up only once
+    let mut records = std::collections::HashMap::new();
+    // Each entry has the domains from the queried one to the including one, to detect loops
+    let mut queue = std::collections::VecDeque::from([(
+        report.name.clone(),
+        terms,
+        vec![report.name.to_ascii_lowercase()],
+    )]);
+    while let Some((domain, terms, path)) = queue.pop_front() {
+        for term in terms {
+            match term.mechanism.as_str() {
+                "a" | "mx" | "exists" => spf.lookups += 1,
+                "ptr" => {
+                    spf.lookups += 1;
+                    report.push(
+                        Severity::Warning,
+                        format!("ptr mechanism in SPF of {} is deprecated", domain),
+                    );
+                }
+                "include" | "redirect" => {
+                    spf.lookups += 1;
+                    let Some(target) = term.value else {
+                        report.push(
+                            Severity::Error,
+                            format!("{} without domain in SPF of {}", term.mechanism, domain),
+                        );
+                        continue;
+                    };
+                    let target = target.trim_end_matches('.').to_ascii_lowercase();
+                    if target.contains('%') {
+                        // Macros depend on the sender
+                        continue;
+                    }
+                    if path.contains(&target) {
+                        report.push(
+                            Severity::Error,
+                            format!("SPF include loop: {} -> {}", path.join(" -> "), target),
+                        );
+                        continue;
+                    }
+                    if !spf.includes.contains(&target) {
+                        spf.includes.push(target.clone());
+                    }
+                    if spf.lookups > SPF_LOOKUP_LIMIT {
+                        // Exceeded anyway, so stop expanding
+                        continue;
+                    }
+                    if !records.contains_key(&target) {
+                        let terms =
+                            match lookup_txt(resolver, &format!("{}.", target), "v=spf1").await {
+                                Ok(mut records) if records.len() == 1 => {
+                                    Some(parse_spf(&records.pop().unwrap()))
+                                }
+                                Ok(records) if records.is_empty() => {
+                                    report.push(
+                                        Severity::Error,
+                                        format!(
+                                            "{} has no SPF record but is referenced by {}",
+                                            target, domain
+                                        ),
+                                    );
+                                    None
+                                }
+                                Ok(_) => {
+                                    report.push(
+                                        Severity::Error,
+                                        format!("{} has multiple SPF records", target),
+                                    );
+                                    None
+                                }
+                                Err(e) => {
+                                    report.push(
+                                        Severity::Error,
+                                        format!("failed to look up SPF of {}: {}", target, e),
+                                    );
+                                    None
+                                }
+                            };
+                        records.insert(target.clone(), terms);
+                    }
+                    if let Some(terms) = &records[&target] {
+                        let mut path = path.clone();
+                        path.push(target.clone());
+                        queue.push_back((target, terms.clone(), path));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    if spf.lookups > SPF_LOOKUP_LIMIT {
+        report.push(
+            Severity::Error,
+            format!(
+                "SPF requires {} DNS lookups, exceeding the limit of {}",
+                spf.lookups, SPF_LOOKUP_LIMIT
+            ),
+        );
+    }
+    report.spf = Some(spf);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SpfTerm {
+    qualifier: char,
+    /// Lowercased mechanism or modifier name
+    mechanism: String,
+    value: Option<String>,
+}
+
+fn parse_spf(record: &str) -> Vec<SpfTerm> {
+    record
+        .split_ascii_whitespace()
+        .skip(1)
+        .map(|term| {
+            let (qualifier, term) = match term.chars().next() {
+                Some(c @ ('+' | '-' | '~' | '?')) => (c, &term[1..]),
+                _ => ('+', term),
+            };
+            let (mechanism, value) = match term.find([':', '=', '/']) {
+                Some(i) if term.as_bytes()[i] == b'/' => (&term[..i], None),
+                Some(i) => (&term[..i], Some(&term[i + 1..])),
+                None => (term, None),
+            };
+            SpfTerm {
+                qualifier,
+                mechanism: mechanism.to_ascii_lowercase(),
+                value: value.map(str::to_owned),
+            }
+        })
+        .collect()
+}
+
+fn check_dmarc(report: &mut EmailReport, mut records: Vec<String>) {
+    if records.len() > 1 {
+        report.push(
+            Severity::Error,
+            "multiple DMARC records are published, which disables DMARC".to_owned(),
+        );
+    }
+    let Some(record) = records.pop() else {
+        report.push(Severity::Error, "no DMARC record".to_owned());
+        return;
+    };
+
+    let tags = parse_tags(&record);
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    };
+    match tag("p").map(|p| p.to_ascii_lowercase()).as_deref() {
+        Some("reject") | Some("quarantine") => {}
+        Some("none") => report.push(
+            Severity::Warning,
+            "DMARC policy is none, so failing mail is only monitored".to_owned(),
+        ),
+        Some(p) => report.push(Severity::Error, format!("invalid DMARC policy {}", p)),
+        None => report.push(Severity::Error, "DMARC record has no policy".to_owned()),
+    }
+    if let Some(pct) = tag("pct")
+        && pct != "100"
+    {
+        report.push(
+            Severity::Warning,
+            format!("DMARC policy applies to {}% of failing mail", pct),
+        );
+    }
+    if tag("rua").is_none() {
+        report.push(
+            Severity::Warning,
+            "DMARC has no rua, so no aggregate reports are sent".to_owned(),
+        );
+    }
+    report.dmarc = Some(record);
+}
+
+fn check_dkim(report: &mut EmailReport, selector: &str, record: &str) {
+    let tags = parse_tags(record);
+    let Some((_, key)) = tags.iter().find(|(key, _)| *key == "p") else {
+        report.push(
+            Severity::Error,
+            format!("DKIM selector {} has no public key", selector),
+        );
+        return;
+    };
+    if key.is_empty() {
+        report.push(
+            Severity::Info,
+            format!("DKIM key of selector {} is revoked", selector),
+        );
+        return;
+    }
+    let is_rsa = tags
+        .iter()
+        .find(|(key, _)| *key == "k")
+        .is_none_or(|(_, k)| k.eq_ignore_ascii_case("rsa"));
+    let key: String = key.split_ascii_whitespace().collect();
+    match data_encoding::BASE64.decode(key.as_bytes()) {
+        // SubjectPublicKeyInfo of a 1024-bit RSA key is 162 bytes
+        Ok(der) if is_rsa && der.len() < 162 => report.push(
+            Severity::Warning,
+            format!(
+                "DKIM key of selector {} is shorter than 1024 bits",
+                selector
+            ),
+        ),
+        Ok(_) => {}
+        Err(e) => report.push(
+            Severity::Error,
+            format!("DKIM key of selector {} is invalid: {}", selector, e),
+        ),
+    }
+}
+
+/// Parse `key=value; key=value` used by DMARC, DKIM and MTA-STS
+fn parse_tags(record: &str) -> Vec<(&str, &str)> {
+    record
+        .split(';')
+        .filter_map(|tag| {
+            let (key, value) = tag.split_once('=')?;
+            Some((key.trim(), value.trim()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse_spf() {
+        let terms = super::parse_spf("v=spf1 ip4:192.0.2.0/24 a/24 -include:_spf.example.com ~all");
+        let terms: Vec<_> = terms
+            .iter()
+            .map(|term| {
+                (
+                    term.qualifier,
+                    term.mechanism.as_str(),
+                    term.value.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            terms,
+            vec![
+                ('+', "ip4", Some("192.0.2.0/24")),
+                ('+', "a", None),
+                ('-', "include", Some("_spf.example.com")),
+                ('~', "all", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_dmarc() {
+        let mut report = super::EmailReport {
+            name: "example.com".to_owned(),
+            spf: None,
+            dmarc: None,
+            dkim: Vec::new(),
+            mta_sts: None,
+            findings: Vec::new(),
+        };
+        super::check_dmarc(&mut report, vec!["v=DMARC1; p=none; pct=50".to_owned()]);
+        let findings: Vec<_> = report.findings.iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            findings,
+            vec![
+                "warning: DMARC policy is none, so failing mail is only monitored",
+                "warning: DMARC policy applies to 50% of failing mail",
+                "warning: DMARC has no rua, so no aggregate reports are sent",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn expand_spf() {
+        use hickory_resolver::proto::rr::{RData, Record, RecordType, rdata};
+
+        let addr = crate::stub::stub_server(([127, 0, 0, 1], 0).into(), |query, response| {
+            response.set_authoritative(true);
+            let text = match query.name().to_ascii().as_str() {
+                "example." => "v=spf1 include:_spf.example include:missing.example -all",
+                "_spf.example." => "v=spf1 a mx ~all",
+                _ => return,
+            };
+            if query.query_type() == RecordType::TXT {
+                response.add_answer(Record::from_rdata(
+                    query.name().clone(),
+                    300,
+                    RData::TXT(rdata::TXT::new(vec![text.to_owned()])),
+                ));
+            }
+        })
+        .await;
+        let resolver = crate::stub::resolver(addr);
+
+        let report = super::check_email(&resolver, "example.").await;
+        let spf = report.spf.unwrap();
+        assert_eq!(spf.lookups, 4);
+        assert_eq!(spf.includes, vec!["_spf.example", "missing.example"]);
+        assert!(report.findings.contains(&super::Finding {
+            severity: super::Severity::Error,
+            message: "missing.example has no SPF record but is referenced by example".to_owned(),
+        }));
+        assert!(report.findings.contains(&super::Finding {
+            severity: super::Severity::Error,
+            message: "no DMARC record".to_owned(),
+        }));
+    }
+
+    #[tokio::test]
+    async fn spf_include_paths() {
+        use hickory_resolver::proto::rr::{RData, Record, RecordType, rdata};
+
+        let addr = crate::stub::stub_server(([127, 0, 0, 1], 0).into(), |query, response| {
+            response.set_authoritative(true);
+            let text = match query.name().to_ascii().as_str() {
+                "diamond." => "v=spf1 include:a.diamond include:b.diamond -all",
+                "a.diamond." | "b.diamond." => "v=spf1 include:common.diamond",
+                "common.diamond." => "v=spf1 ip4:192.0.2.0/24 a",
+                "loop." => "v=spf1 include:inner.loop -all",
+                "inner.loop." => "v=spf1 include:LOOP.",
+                _ => return,
+            };
+            if query.query_type() == RecordType::TXT {
+                response.add_answer(Record::from_rdata(
+                    query.name().clone(),
+                    300,
+                    RData::TXT(rdata::TXT::new(vec![text.to_owned()])),
+                ));
+            }
+        })
+        .await;
+        let resolver = crate::stub::resolver(addr);
+        let spf_errors = |report: &super::EmailReport| {
+            report
+                .findings
+                .iter()
+                .filter(|f| f.severity == super::Severity::Error && f.message != "no DMARC record")
+                .map(|f| f.message.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Included from two branches, which is evaluated and counted twice
+        let report = super::check_email(&resolver, "diamond.").await;
+        assert_eq!(spf_errors(&report), Vec::<String>::new());
+        let spf = report.spf.unwrap();
+        assert_eq!(spf.lookups, 6);
+        assert_eq!(
+            spf.includes,
+            vec!["a.diamond", "b.diamond", "common.diamond"]
+        );
+
+        let report = super::check_email(&resolver, "loop.").await;
+        assert_eq!(
+            spf_errors(&report),
+            vec!["SPF include loop: loop -> inner.loop -> loop"]
+        );
+    }
+}
@@ -0,0 +1,933 @@
+pub mod cname;
+pub mod email;
+pub mod trace;
+pub mod watch;
+
+#[cfg(test)]
+mod stub;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, clap::ValueEnum, serde::Serialize,
+)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum QueryType {
+    Mx,
+    Txt,
+    Caa,
+    Ns,
+    Soa,
+    Srv,
+    Https,
+    Svcb,
+    Tlsa,
+    Ds,
+    Dnskey,
+    Cname,
+    A,
+    Aaaa,
+    Ptr,
+}
+
+impl QueryType {
+    pub fn record_type(self) -> hickory_resolver::proto::rr::RecordType {
+        use hickory_resolver::proto::rr::RecordType;
+        match self {
+            Self::Mx => RecordType::MX,
+            Self::Txt => RecordType::TXT,
+            Self::Caa => RecordType::CAA,
+            Self::Ns => RecordType::NS,
+            Self::Soa => RecordType::SOA,
+            Self::Srv => RecordType::SRV,
+            Self::Https => RecordType::HTTPS,
+            Self::Svcb => RecordType::SVCB,
+            Self::Tlsa => RecordType::TLSA,
+            Self::Ds => RecordType::DS,
+            Self::Dnskey => RecordType::DNSKEY,
+            Self::Cname => RecordType::CNAME,
+            Self::A => RecordType::A,
+            Self::Aaaa => RecordType::AAAA,
+            Self::Ptr => RecordType::PTR,
+        }
+    }
+}
+
+/// Records found for the queried name. Records are grouped by type and ordered deterministically
+#[derive(Debug, serde::Serialize)]
+pub struct NameResult {
+    pub name: String,
+    pub records: std::collections::BTreeMap<QueryType, Vec<Record>>,
+    /// Lookups which failed other than NXDOMAIN and NODATA, e.g. timeouts and SERVFAIL
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub errors: std::collections::BTreeMap<QueryType, String>,
+    /// Zones from the root to the queried name, present when DNSSEC validation is enabled
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<Zone>,
+    /// Response of the lookups of each type
+    pub queries: std::collections::BTreeMap<QueryType, QueryStatus>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct QueryStatus {
+    /// Response code, e.g. "Non-Existent Domain". `None` when no response was received, e.g. on
+    /// timeouts. Types looked up with multiple queries (CNAME and PTR) have the first failure
+    pub rcode: Option<String>,
+    /// Total time of the queries of the type
+    pub elapsed_ms: u64,
+    /// Name server which responded, e.g. "1.1.1.1:53" or "https:cloudflare-dns.com@1.1.1.1:443".
+    /// `None` when answered from the cache or no response was received. Types looked up with
+    /// multiple queries have the server of the first failure or the last response
+    pub server: Option<String>,
+}
+
+impl QueryStatus {
+    /// Merge the status of another query of the same type
+    fn add(&mut self, other: Self) {
+        self.elapsed_ms += other.elapsed_ms;
+        if self.rcode.as_deref() == Some(NO_ERROR) {
+            self.rcode = other.rcode;
+            if other.server.is_some() {
+                self.server = other.server;
+            }
+        }
+    }
+}
+
+tokio::task_local! {
+    /// Name server which responded last in the lookup, set by [`RecordingConnectionProvider`]
+    static RESPONDED_SERVER: std::cell::Cell<Option<std::sync::Arc<str>>>;
+}
+
+/// Run the lookup and return the name server which responded to it, which hickory-resolver
+/// doesn't tell
+async fn with_responded_server<F>(lookup: F) -> (F::Output, Option<String>)
+where
+    F: std::future::Future,
+{
+    RESPONDED_SERVER
+        .scope(std::cell::Cell::new(None), async {
+            let output = lookup.await;
+            let server = RESPONDED_SERVER.with(|server| server.take());
+            (output, server.map(|server| server.to_string()))
+        })
+        .await
+}
+
+/// Connection provider recording the name server of each response for [`QueryStatus::server`]
+#[derive(Debug, Clone, Default)]
+pub struct RecordingConnectionProvider<P>(pub P);
+
+impl<P> hickory_resolver::name_server::ConnectionProvider for RecordingConnectionProvider<P>
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    type Conn = RecordingConnection<P::Conn>;
+    type FutureConn = futures::future::BoxFuture<
+        'static,
+        Result<Self::Conn, hickory_resolver::proto::ProtoError>,
+    >;
+    type RuntimeProvider = P::RuntimeProvider;
+
+    fn new_connection(
+        &self,
+        config: &hickory_resolver::config::NameServerConfig,
+        options: &hickory_resolver::config::ResolverOpts,
+    ) -> Result<Self::FutureConn, std::io::Error> {
+        let server = std::sync::Arc::from(server_name(config));
+        let conn = self.0.new_connection(config, options)?;
+        Ok(Box::pin(async move {
+            Ok(RecordingConnection {
+                inner: conn.await?,
+                server,
+            })
+        }))
+    }
+}
+
+/// e.g. "192.0.2.1:53" for UDP and "tls:dns.google@8.8.8.8:853", like the resolver options of
+/// the command
+fn server_name(config: &hickory_resolver::config::NameServerConfig) -> String {
+    match (config.protocol, &config.tls_dns_name) {
+        (hickory_resolver::proto::xfer::Protocol::Udp, _) => config.socket_addr.to_string(),
+        (protocol, Some(name)) => format!("{}:{}@{}", protocol, name, config.socket_addr),
+        (protocol, None) => format!("{}:{}", protocol, config.socket_addr),
+    }
+}
+
+/// Connection of [`RecordingConnectionProvider`]
+#[derive(Debug, Clone)]
+pub struct RecordingConnection<C> {
+    inner: C,
+    server: std::sync::Arc<str>,
+}
+
+impl<C> hickory_resolver::proto::xfer::DnsHandle for RecordingConnection<C>
+where
+    C: hickory_resolver::proto::xfer::DnsHandle,
+{
+    type Response = futures::stream::BoxStream<
+        'static,
+        Result<hickory_resolver::proto::xfer::DnsResponse, hickory_resolver::proto::ProtoError>,
+    >;
+
+    fn is_verifying_dnssec(&self) -> bool {
+        self.inner.is_verifying_dnssec()
+    }
+
+    fn is_using_edns(&self) -> bool {
+        self.inner.is_using_edns()
+    }
+
+    fn send<R>(&self, request: R) -> Self::Response
+    where
+        R: Into<hickory_resolver::proto::xfer::DnsRequest> + Unpin + Send + 'static,
+    {
+        use futures::StreamExt as _;
+
+        let server = self.server.clone();
+        // Responses are received in the task of the lookup
+        self.inner
+            .send(request)
+            .inspect(move |response| {
+                if response.is_ok() {
+                    let _ = RESPONDED_SERVER.try_with(|s| s.set(Some(server.clone())));
+                }
+            })
+            .boxed()
+    }
+}
+
+const NO_ERROR: &str = "No Error";
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Record {
+    /// Owner name of the record, or the address for PTR records
+    pub name: String,
+    pub ttl: u32,
+    #[serde(flatten)]
+    pub data: RecordData,
+    /// Validation status, present when DNSSEC validation is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof: Option<Proof>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Proof {
+    Secure,
+    Insecure,
+    Bogus,
+    Indeterminate,
+}
+
+impl From<hickory_resolver::proto::dnssec::Proof> for Proof {
+    fn from(proof: hickory_resolver::proto::dnssec::Proof) -> Self {
+        use hickory_resolver::proto::dnssec::Proof;
+        match proof {
+            Proof::Secure => Self::Secure,
+            Proof::Insecure => Self::Insecure,
+            Proof::Bogus => Self::Bogus,
+            Proof::Indeterminate => Self::Indeterminate,
+        }
+    }
+}
+
+impl std::fmt::Display for Proof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Secure => "secure",
+            Self::Insecure => "insecure",
+            Self::Bogus => "bogus",
+            Self::Indeterminate => "indeterminate",
+        })
+    }
+}
+
+/// DS records in the parent zone and DNSKEY records of the zone. The root zone has no DS records
+/// since its keys are trust anchors
+#[derive(Debug, serde::Serialize)]
+pub struct Zone {
+    pub name: String,
+    pub ds: Vec<Record>,
+    pub dnskey: Vec<Record>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(untagged)]
+pub enum RecordData {
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    Txt {
+        text: String,
+    },
+    Caa {
+        tag: String,
+        value: String,
+        critical: bool,
+    },
+    Ns {
+        nameserver: String,
+    },
+    Soa {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: i32,
+        retry: i32,
+        expire: i32,
+        minimum: u32,
+    },
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    /// HTTPS and SVCB records. `params` are in the presentation format, e.g. `alpn=h2,h3`
+    Svcb {
+        priority: u16,
+        target: String,
+        params: Vec<String>,
+    },
+    /// `data` is hex-encoded
+    Tlsa {
+        usage: u8,
+        selector: u8,
+        matching: u8,
+        data: String,
+    },
+    /// `digest` is hex-encoded
+    Ds {
+        key_tag: u16,
+        algorithm: u8,
+        digest_type: u8,
+        digest: String,
+    },
+    /// `public_key` is base64-encoded
+    Dnskey {
+        flags: u16,
+        algorithm: u8,
+        key_tag: u16,
+        public_key: String,
+    },
+    Cname {
+        target: String,
+    },
+    Address {
+        address: std::net::IpAddr,
+    },
+    /// `target` is `None` when the address has no PTR records
+    Ptr {
+        target: Option<String>,
+    },
+}
+
+impl RecordData {
+    /// The address, the target name or the text of the record, excluding parameters such as MX
+    /// preferences and SRV ports. `None` for SOA records
+    pub fn value(&self) -> Option<String> {
+        match self {
+            Self::Mx { exchange, .. } => Some(exchange.clone()),
+            Self::Txt { text } => Some(text.clone()),
+            Self::Caa { value, .. } => Some(value.clone()),
+            Self::Ns { nameserver } => Some(nameserver.clone()),
+            Self::Soa { .. } => None,
+            Self::Srv { target, .. } | Self::Svcb { target, .. } | Self::Cname { target } => {
+                Some(target.clone())
+            }
+            Self::Tlsa { data, .. } => Some(data.clone()),
+            Self::Ds { digest, .. } => Some(digest.clone()),
+            Self::Dnskey { public_key, .. } => Some(public_key.clone()),
+            Self::Address { address } => Some(address.to_string()),
+            Self::Ptr { target } => target.clone(),
+        }
+    }
+}
+
+/// Limit of CNAME chain to avoid looping forever
+const MAX_CNAME_CHAIN: usize = 16;
+
+/// Query the records of `types`. CNAME chain is followed to look up addresses, and addresses are
+/// looked up to query PTR records
+pub async fn lookup_name<P>(
+    resolver: &hickory_resolver::Resolver<P>,
+    name: &str,
+    types: &[QueryType],
+) -> NameResult
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    let mut results = std::collections::BTreeMap::new();
+    // CNAME, addresses and PTR are queried below
+    let mut tasks = tokio::task::JoinSet::new();
+    for &query_type in types {
+        if !matches!(
+            query_type,
+            QueryType::Cname | QueryType::A | QueryType::Aaaa | QueryType::Ptr
+        ) {
+            let resolver = resolver.clone();
+            let name = name.to_owned();
+            tasks.spawn(async move {
+                (
+                    query_type,
+                    query_records(&resolver, &name, query_type).await,
+                )
+            });
+        }
+    }
+    while let Some(result) = tasks.join_next().await {
+        let (query_type, result) = result.expect("lookup panicked");
+        results.insert(query_type, result);
+    }
+
+    let query_ptr = types.contains(&QueryType::Ptr);
+    if types.contains(&QueryType::Cname)
+        || types.contains(&QueryType::A)
+        || types.contains(&QueryType::Aaaa)
+        || query_ptr
+    {
+        let mut cnames = Ok(Vec::new());
+        let mut cname_status: Option<QueryStatus> = None;
+        let mut canonical_name = name.to_owned();
+        for _ in 0..MAX_CNAME_CHAIN {
+            let (result, status) = query_records(resolver, &canonical_name, QueryType::Cname).await;
+            match cname_status {
+                Some(ref mut s) => s.add(status),
+                None => cname_status = Some(status),
+            }
+            let cname = match result {
+                Ok(records) => records.into_iter().next(),
+                Err(e) => {
+                    cnames = Err(e);
+                    break;
+                }
+            };
+            let Some(cname) = cname else {
+                break;
+            };
+            let RecordData::Cname { ref target } = cname.data else {
+                unreachable!()
+            };
+            canonical_name = target.clone();
+            if let Ok(ref mut cnames) = cnames {
+                cnames.push(cname);
+            }
+        }
+        if types.contains(&QueryType::Cname) {
+            results.insert(
+                QueryType::Cname,
+                (
+                    cnames,
+                    cname_status.expect("CNAME is queried at least once"),
+                ),
+            );
+        }
+
+        let (a, aaaa) = tokio::join!(
+            async {
+                if types.contains(&QueryType::A) || query_ptr {
+                    Some(query_records(resolver, &canonical_name, QueryType::A).await)
+                } else {
+                    None
+                }
+            },
+            async {
+                if types.contains(&QueryType::Aaaa) || query_ptr {
+                    Some(query_records(resolver, &canonical_name, QueryType::Aaaa).await)
+                } else {
+                    None
+                }
+            },
+        );
+        if query_ptr {
+            let mut ptrs = Ok(Vec::new());
+            let mut ptr_status = QueryStatus {
+                rcode: Some(NO_ERROR.to_owned()),
+                elapsed_ms: 0,
+                server: None,
+            };
+            let mut tasks = tokio::task::JoinSet::new();
+            for (result, _) in a.iter().chain(aaaa.iter()) {
+                for record in result.iter().flatten() {
+                    if let RecordData::Address { address } = record.data {
+                        let resolver = resolver.clone();
+                        tasks.spawn(async move { lookup_ptr(&resolver, address).await });
+                    }
+                }
+            }
+            while let Some(result) = tasks.join_next().await {
+                let (result, status) = result.expect("PTR lookup panicked");
+                ptr_status.add(status);
+                match (result, &mut ptrs) {
+                    (Ok(records), Ok(ptrs)) => ptrs.extend(records),
+                    (Err(e), Ok(_)) => ptrs = Err(e),
+                    (_, Err(_)) => {}
+                }
+            }
+            if let Ok(ref mut ptrs) = ptrs {
+                sort_records(ptrs);
+            }
+            results.insert(QueryType::Ptr, (ptrs, ptr_status));
+        }
+        if let Some(a) = a
+            && types.contains(&QueryType::A)
+        {
+            results.insert(QueryType::A, a);
+        }
+        if let Some(aaaa) = aaaa
+            && types.contains(&QueryType::Aaaa)
+        {
+            results.insert(QueryType::Aaaa, aaaa);
+        }
+    }
+
+    let mut records = std::collections::BTreeMap::new();
+    let mut errors = std::collections::BTreeMap::new();
+    let mut queries = std::collections::BTreeMap::new();
+    for (query_type, (result, status)) in results {
+        match result {
+            Ok(result) => {
+                records.insert(query_type, result);
+            }
+            Err(e) => {
+                errors.insert(query_type, e);
+            }
+        }
+        queries.insert(query_type, status);
+    }
+    NameResult {
+        name: name.to_owned(),
+        records,
+        errors,
+        chain: lookup_chain(resolver, name).await,
+        queries,
+    }
+}
+
+/// Look up DS and DNSKEY records of zones enclosing `name` when DNSSEC validation is enabled.
+/// Names without DNSKEY records are not zone apexes and skipped
+async fn lookup_chain<P>(resolver: &hickory_resolver::Resolver<P>, name: &str) -> Vec<Zone>
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    if !resolver.options().validate {
+        return Vec::new();
+    }
+    let Ok(mut name) = hickory_resolver::Name::from_utf8(name) else {
+        return Vec::new();
+    };
+    name.set_fqdn(true);
+
+    let mut tasks = tokio::task::JoinSet::new();
+    loop {
+        let resolver = resolver.clone();
+        let zone = name.to_utf8();
+        let is_root = name.is_root();
+        let num_labels = name.num_labels();
+        tasks.spawn(async move {
+            let (ds, dnskey) = tokio::join!(
+                async {
+                    if is_root {
+                        Vec::new()
+                    } else {
+                        lookup_records(&resolver, &zone, QueryType::Ds)
+                            .await
+                            .unwrap_or_default()
+                    }
+                },
+                async {
+                    lookup_records(&resolver, &zone, QueryType::Dnskey)
+                        .await
+                        .unwrap_or_default()
+                }
+            );
+            (
+                num_labels,
+                Zone {
+                    name: zone,
+                    ds,
+                    dnskey,
+                },
+            )
+        });
+        if is_root {
+            break;
+        }
+        name = name.base_name();
+    }
+
+    let mut zones = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        let (num_labels, zone) = result.expect("chain lookup panicked");
+        if !zone.dnskey.is_empty() {
+            zones.push((num_labels, zone));
+        }
+    }
+    zones.sort_by_key(|(num_labels, _)| *num_labels);
+    zones.into_iter().map(|(_, zone)| zone).collect()
+}
+
+/// Query types whose answers differ among `results` of the same name. TTLs are ignored since
+/// caching resolvers decrease them
+pub fn disagreements(results: &[NameResult]) -> Vec<QueryType> {
+    let mut types: Vec<_> = results
+        .iter()
+        .flat_map(|result| result.records.keys().copied())
+        .collect();
+    types.sort();
+    types.dedup();
+    types
+        .into_iter()
+        .filter(|query_type| {
+            let answers: Vec<Vec<&RecordData>> = results
+                .iter()
+                .map(|result| {
+                    result
+                        .records
+                        .get(query_type)
+                        .map(|records| records.iter().map(|record| &record.data).collect())
+                        .unwrap_or_default()
+                })
+                .collect();
+            answers.windows(2).any(|pair| pair[0] != pair[1])
+        })
+        .collect()
+}
+
+/// NXDOMAIN and NODATA are not errors but no records
+async fn lookup_records<P>(
+    resolver: &hickory_resolver::Resolver<P>,
+    name: &str,
+    query_type: QueryType,
+) -> Result<Vec<Record>, String>
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    query_records(resolver, name, query_type).await.0
+}
+
+/// Same as `lookup_records`, with the response code and the time taken
+async fn query_records<P>(
+    resolver: &hickory_resolver::Resolver<P>,
+    name: &str,
+    query_type: QueryType,
+) -> (Result<Vec<Record>, String>, QueryStatus)
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    let record_type = query_type.record_type();
+    let start = std::time::Instant::now();
+    let (result, server) = with_responded_server(resolver.lookup(name, record_type)).await;
+    let mut status = QueryStatus {
+        rcode: Some(NO_ERROR.to_owned()),
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        server,
+    };
+    let lookup = match result {
+        Ok(lookup) => lookup,
+        Err(e) => {
+            status.rcode = response_code(&e).map(|code| code.to_string());
+            return (ignore_no_records(e).map(|()| Vec::new()), status);
+        }
+    };
+    let validate = resolver.options().validate;
+    let mut records: Vec<_> = lookup
+        .record_iter()
+        .filter(|record| record.record_type() == record_type)
+        .filter_map(|record| {
+            Some(Record {
+                // Owner name, which differs from `name` when CNAME is followed
+                name: record.name().to_utf8(),
+                ttl: record.ttl(),
+                data: to_record_data(record.data())?,
+                proof: validate.then(|| record.proof().into()),
+            })
+        })
+        .collect();
+    if query_type != QueryType::Cname {
+        sort_records(&mut records);
+    }
+    (Ok(records), status)
+}
+
+/// Response code of the failed lookup. `None` when no response was received
+fn response_code(
+    e: &hickory_resolver::ResolveError,
+) -> Option<hickory_resolver::proto::op::ResponseCode> {
+    match e.proto().map(|e| e.kind()) {
+        Some(hickory_resolver::proto::ProtoErrorKind::NoRecordsFound { response_code, .. }) => {
+            Some(*response_code)
+        }
+        _ => None,
+    }
+}
+
+fn ignore_no_records(e: hickory_resolver::ResolveError) -> Result<(), String> {
+    use hickory_resolver::proto::op::ResponseCode;
+
+    match response_code(&e) {
+        Some(ResponseCode::NoError | ResponseCode::NXDomain) => Ok(()),
+        _ => Err(e.to_string()),
+    }
+}
+
+async fn lookup_ptr<P>(
+    resolver: &hickory_resolver::Resolver<P>,
+    address: std::net::IpAddr,
+) -> (Result<Vec<Record>, String>, QueryStatus)
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    let validate = resolver.options().validate;
+    let mut records = Vec::new();
+    let start = std::time::Instant::now();
+    let (result, server) = with_responded_server(resolver.reverse_lookup(address)).await;
+    let mut status = QueryStatus {
+        rcode: Some(NO_ERROR.to_owned()),
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        server,
+    };
+    match result {
+        Ok(lookup) => {
+            for record in lookup.as_lookup().record_iter() {
+                if let Some(ptr) = record.data().as_ptr() {
+                    records.push(Record {
+                        name: address.to_string(),
+                        ttl: record.ttl(),
+                        data: RecordData::Ptr {
+                            target: Some(ptr.to_string()),
+                        },
+                        proof: validate.then(|| record.proof().into()),
+                    });
+                }
+            }
+        }
+        Err(e) => {
+            status.rcode = response_code(&e).map(|code| code.to_string());
+            if let Err(e) = ignore_no_records(e) {
+                return (Err(e), status);
+            }
+        }
+    }
+    if records.is_empty() {
+        records.push(Record {
+            name: address.to_string(),
+            ttl: 0,
+            data: RecordData::Ptr { target: None },
+            proof: None,
+        });
+    }
+    (Ok(records), status)
+}
+
+fn sort_records(records: &mut [Record]) {
+    records.sort_by(|x, y| x.name.cmp(&y.name).then_with(|| x.data.cmp(&y.data)));
+}
+
+pub(crate) fn to_record_data(rdata: &hickory_resolver::proto::rr::RData) -> Option<RecordData> {
+    use hickory_resolver::proto::dnssec::PublicKey as _;
+    use hickory_resolver::proto::dnssec::rdata::DNSSECRData;
+    use hickory_resolver::proto::rr::RData;
+    use hickory_resolver::proto::rr::rdata::caa::Property;
+
+    Some(match rdata {
+        RData::MX(mx) => RecordData::Mx {
+            preference: mx.preference(),
+            exchange: mx.exchange().to_utf8(),
+        },
+        RData::TXT(txt) => RecordData::Txt {
+            // Long TXT records are split into multiple strings
+            text: txt
+                .txt_data()
+                .iter()
+                .map(|data| String::from_utf8_lossy(data))
+                .collect(),
+        },
+        RData::CAA(caa) => {
+            let value = match caa.tag() {
+                Property::Issue | Property::IssueWild => caa
+                    .value_as_issue()
+                    .ok()
+                    .and_then(|(domain, _)| domain)
+                    .map(|domain| domain.to_utf8()),
+                Property::Iodef => caa.value_as_iodef().ok().map(|url| url.to_string()),
+                _ => None,
+            };
+            RecordData::Caa {
+                tag: caa.tag().as_str().to_owned(),
+                value: value
+                    .unwrap_or_else(|| String::from_utf8_lossy(caa.raw_value()).into_owned()),
+                critical: caa.issuer_critical(),
+            }
+        }
+        RData::NS(ns) => RecordData::Ns {
+            nameserver: ns.to_utf8(),
+        },
+        RData::SOA(soa) => RecordData::Soa {
+            mname: soa.mname().to_utf8(),
+            rname: soa.rname().to_utf8(),
+            serial: soa.serial(),
+            refresh: soa.refresh(),
+            retry: soa.retry(),
+            expire: soa.expire(),
+            minimum: soa.minimum(),
+        },
+        RData::SRV(srv) => RecordData::Srv {
+            priority: srv.priority(),
+            weight: srv.weight(),
+            port: srv.port(),
+            target: srv.target().to_utf8(),
+        },
+        RData::HTTPS(hickory_resolver::proto::rr::rdata::HTTPS(svcb)) | RData::SVCB(svcb) => {
+            RecordData::Svcb {
+                priority: svcb.svc_priority(),
+                target: svcb.target_name().to_utf8(),
+                params: svcb
+                    .svc_params()
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect(),
+            }
+        }
+        RData::TLSA(tlsa) => RecordData::Tlsa {
+            usage: tlsa.cert_usage().into(),
+            selector: tlsa.selector().into(),
+            matching: tlsa.matching().into(),
+            data: data_encoding::HEXLOWER.encode(tlsa.cert_data()),
+        },
+        RData::DNSSEC(DNSSECRData::DS(ds)) => RecordData::Ds {
+            key_tag: ds.key_tag(),
+            algorithm: ds.algorithm().into(),
+            digest_type: ds.digest_type().into(),
+            digest: data_encoding::HEXLOWER.encode(ds.digest()),
+        },
+        RData::DNSSEC(DNSSECRData::DNSKEY(dnskey)) => RecordData::Dnskey {
+            flags: dnskey.flags(),
+            algorithm: dnskey.public_key().algorithm().into(),
+            key_tag: dnskey.calculate_key_tag().ok()?,
+            public_key: data_encoding::BASE64.encode(dnskey.public_key().public_bytes()),
+        },
+        RData::CNAME(cname) => RecordData::Cname {
+            target: cname.to_string(),
+        },
+        RData::A(a) => RecordData::Address {
+            address: std::net::IpAddr::from(a.0),
+        },
+        RData::AAAA(aaaa) => RecordData::Address {
+            address: std::net::IpAddr::from(aaaa.0),
+        },
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn query_status() {
+        use hickory_resolver::proto::op::ResponseCode;
+
+        let addr = crate::stub::stub_server(([127, 0, 0, 1], 0).into(), |query, response| {
+            response.set_response_code(match query.name().to_ascii().as_str() {
+                "missing.example." => ResponseCode::NXDomain,
+                _ => ResponseCode::ServFail,
+            });
+        })
+        .await;
+        let resolver = crate::stub::resolver(addr);
+
+        let missing =
+            super::lookup_name(&resolver, "missing.example.", &[super::QueryType::Mx]).await;
+        assert_eq!(missing.records[&super::QueryType::Mx], Vec::new());
+        assert_eq!(
+            missing.queries[&super::QueryType::Mx].rcode.as_deref(),
+            Some("Non-Existent Domain")
+        );
+        assert_eq!(
+            missing.queries[&super::QueryType::Mx].server,
+            Some(addr.to_string())
+        );
+        let failed =
+            super::lookup_name(&resolver, "broken.example.", &[super::QueryType::Mx]).await;
+        assert!(failed.errors.contains_key(&super::QueryType::Mx));
+        assert_eq!(
+            failed.queries[&super::QueryType::Mx].rcode.as_deref(),
+            Some("Server Failure")
+        );
+        assert_eq!(
+            failed.queries[&super::QueryType::Mx].server,
+            Some(addr.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn owner_name() {
+        use hickory_resolver::Name;
+        use hickory_resolver::proto::rr::{RData, Record, rdata};
+
+        let addr = crate::stub::stub_server(([127, 0, 0, 1], 0).into(), |query, response| {
+            let target = Name::from_ascii("web.example.").unwrap();
+            response.add_answer(Record::from_rdata(
+                query.name().clone(),
+                300,
+                RData::CNAME(rdata::CNAME(target.clone())),
+            ));
+            response.add_answer(Record::from_rdata(
+                target,
+                300,
+                RData::A(rdata::A::new(192, 0, 2, 1)),
+            ));
+        })
+        .await;
+        let resolver = crate::stub::resolver(addr);
+
+        let records = super::lookup_records(&resolver, "www.example.", super::QueryType::A)
+            .await
+            .unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (r.name.as_str(), &r.data))
+                .collect::<Vec<_>>(),
+            vec![(
+                "web.example.",
+                &super::RecordData::Address {
+                    address: [192, 0, 2, 1].into()
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn json_output() {
+        use hickory_resolver::proto::rr::{RData, Record, RecordType, rdata::A};
+
+        let addr = crate::stub::stub_server(([127, 0, 0, 1], 0).into(), |query, response| {
+            if query.query_type() == RecordType::A {
+                response.add_answer(Record::from_rdata(
+                    query.name().clone(),
+                    300,
+                    RData::A(A::new(192, 0, 2, 1)),
+                ));
+            }
+        })
+        .await;
+        let resolver = crate::stub::resolver(addr);
+        let mut result = super::lookup_name(
+            &resolver,
+            "example.com.",
+            &[super::QueryType::Mx, super::QueryType::A],
+        )
+        .await;
+        // Elapsed time varies
+        for status in result.queries.values_mut() {
+            status.elapsed_ms = 0;
+        }
+        assert_eq!(
+            serde_json::to_string(&result).unwrap(),
+            format!(
+                r#"{{"name":"example.com.","records":{{"MX":[],"A":[{{"name":"example.com.","ttl":300,"address":"192.0.2.1"}}]}},"queries":{{"MX":{{"rcode":"No Error","elapsed_ms":0,"server":"{addr}"}},"A":{{"rcode":"No Error","elapsed_ms":0,"server":"{addr}"}}}}}}"#
+            )
+        );
+    }
+}
@@ -1,280 +1,1163 @@
+#[derive(Debug, clap::Parser)]
+struct Args {
+    #[clap(flatten)]
+    resolver: ResolverArgs,
+    /// Record types to query (default: all)
+    #[clap(short = 't', long = "type", value_enum, value_delimiter = ',')]
+    types: Vec<rdig::QueryType>,
+    /// Print results as JSON Lines, one object per name
+    #[clap(long)]
+    json: bool,
+    /// Validate answers with DNSSEC and show DS/DNSKEY records from the root to the name
+    #[clap(long)]
+    dnssec: bool,
+    /// Resolve iteratively from the root servers and show each delegation step (default type: A).
+    /// The resolver is used only to look up name servers without glue records
+    #[clap(long, conflicts_with = "dnssec")]
+    trace: bool,
+    /// Query all of the resolvers and show answers side by side: system, cloudflare, google, quad9,
+    /// ADDR (UDP), tcp:ADDR, tls:NAME@ADDR or https:// URL
+    #[clap(
+        long,
+        value_name = "RESOLVER",
+        value_delimiter = ',',
+        value_parser = parse_resolver_spec,
+        conflicts_with_all = ["ResolverArgs", "trace"],
+    )]
+    compare: Vec<ResolverSpec>,
+    /// Report dangling CNAME chains, loops, overly long chains and names on cloud providers which can
+    /// be taken over. Exits with 1 when anything is found
+    #[clap(long, conflicts_with_all = ["trace", "compare"])]
+    check_cname: bool,
+    /// Check SPF (with includes expanded), DMARC, DKIM keys of common selectors and MTA-STS of the
+    /// mail domains. Exits with 1 when errors are found
+    #[clap(long, conflicts_with_all = ["trace", "compare", "check_cname"])]
+    email: bool,
+    /// Read names from the file ("-" for stdin), one per line. Empty lines and lines starting with #
+    /// are ignored, and a summary is reported to stderr at the end. Exits with 1 when any lookup
+    /// fails
+    #[clap(short = 'f', long, value_name = "FILE")]
+    file: Option<std::path::PathBuf>,
+    /// Number of names resolved concurrently. Results are printed in the given order
+    #[clap(long, default_value_t = std::num::NonZeroUsize::new(8).unwrap())]
+    concurrency: std::num::NonZeroUsize,
+    /// Maximum number of names started per second
+    #[clap(long, value_parser = parse_rate)]
+    rate: Option<f64>,
+    /// Resolve the names again at the interval (e.g. 30s, 5m) and print only changes: added and
+    /// removed records, TTL resets and failures
+    #[clap(
+        long,
+        value_name = "INTERVAL",
+        value_parser = parse_interval,
+        conflicts_with_all = ["trace", "compare", "check_cname", "email"],
+    )]
+    watch: Option<std::time::Duration>,
+    /// Exit once every name has a record with the value (e.g. an address or a CNAME target)
+    #[clap(long, value_name = "VALUE", requires = "watch")]
+    until: Option<String>,
+    /// Show TTLs, the response code and time of each query and full error messages. Repeat (-vv)
+    /// to log DNS messages
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    names: Vec<String>,
+}
+
+/// Cloudflare DNS over HTTPS is used by default
+#[derive(Debug, clap::Args)]
+#[group(multiple = false)]
+struct ResolverArgs {
+    /// Use the system configuration (/etc/resolv.conf)
+    #[clap(long)]
+    system: bool,
+    /// Query the server over UDP (e.g. 192.0.2.1 or 192.0.2.1:53)
+    #[clap(long, value_name = "ADDR", value_parser = parse_server_addr::<53>)]
+    udp: Option<std::net::SocketAddr>,
+    /// Query the server over TCP
+    #[clap(long, value_name = "ADDR", value_parser = parse_server_addr::<53>)]
+    tcp: Option<std::net::SocketAddr>,
+    /// Query the server over TLS. The server name is given as NAME@ADDR (e.g. one.one.one.one@1.1.1.1)
+    #[clap(long, value_name = "NAME@ADDR", value_parser = parse_tls_server)]
+    tls: Option<(String, std::net::SocketAddr)>,
+    /// Query the server over HTTPS (e.g. https://dns.google/dns-query)
+    #[clap(long, value_name = "URL")]
+    https: Option<url::Url>,
+}
+
+impl ResolverArgs {
+    fn spec(&self) -> ResolverSpec {
+        if self.system {
+            ResolverSpec::System
+        } else if let Some(addr) = self.udp {
+            ResolverSpec::Udp(addr)
+        } else if let Some(addr) = self.tcp {
+            ResolverSpec::Tcp(addr)
+        } else if let Some((ref name, addr)) = self.tls {
+            ResolverSpec::Tls(name.clone(), addr)
+        } else if let Some(ref url) = self.https {
+            ResolverSpec::Https(url.clone())
+        } else {
+            ResolverSpec::Cloudflare
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ResolverSpec {
+    System,
+    Cloudflare,
+    Google,
+    Quad9,
+    Udp(std::net::SocketAddr),
+    Tcp(std::net::SocketAddr),
+    Tls(String, std::net::SocketAddr),
+    Https(url::Url),
+}
+
+impl std::fmt::Display for ResolverSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::System => write!(f, "system"),
+            Self::Cloudflare => write!(f, "cloudflare"),
+            Self::Google => write!(f, "google"),
+            Self::Quad9 => write!(f, "quad9"),
+            Self::Udp(addr) => write!(f, "{}", addr),
+            Self::Tcp(addr) => write!(f, "tcp:{}", addr),
+            Self::Tls(name, addr) => write!(f, "tls:{}@{}", name, addr),
+            Self::Https(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+fn parse_resolver_spec(s: &str) -> Result<ResolverSpec, String> {
+    Ok(match s {
+        "system" => ResolverSpec::System,
+        "cloudflare" => ResolverSpec::Cloudflare,
+        "google" => ResolverSpec::Google,
+        "quad9" => ResolverSpec::Quad9,
+        _ => {
+            if let Some(addr) = s.strip_prefix("tcp:") {
+                ResolverSpec::Tcp(parse_server_addr::<53>(addr)?)
+            } else if let Some(server) = s.strip_prefix("tls:") {
+                let (name, addr) = parse_tls_server(server)?;
+                ResolverSpec::Tls(name, addr)
+            } else if s.starts_with("https://") {
+                ResolverSpec::Https(s.parse().map_err(|e| format!("invalid URL {}: {}", s, e))?)
+            } else {
+                ResolverSpec::Udp(parse_server_addr::<53>(s)?)
+            }
+        }
+    })
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => Err(format!("rate must be a positive number: {}", s)),
+    }
+}
+
+fn parse_interval(s: &str) -> Result<std::time::Duration, String> {
+    let (n, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let n: u64 = n.parse().map_err(|_| format!("invalid interval: {}", s))?;
+    let secs = match unit {
+        "s" => Some(n),
+        "m" => n.checked_mul(60),
+        "h" => n.checked_mul(3600),
+        _ => return Err(format!("interval must be in s, m or h: {}", s)),
+    }
+    .ok_or_else(|| format!("invalid interval: {}", s))?;
+    if secs == 0 {
+        return Err(format!("interval must be positive: {}", s));
+    }
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+fn read_names(path: &std::path::Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let content = if path == std::path::Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?
+    };
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
+fn parse_server_addr<const PORT: u16>(s: &str) -> Result<std::net::SocketAddr, String> {
+    if let Ok(ip) = s.parse::<std::net::IpAddr>() {
+        Ok(std::net::SocketAddr::new(ip, PORT))
+    } else {
+        s.parse()
+            .map_err(|e| format!("invalid address {}: {}", s, e))
+    }
+}
+
+fn parse_tls_server(s: &str) -> Result<(String, std::net::SocketAddr), String> {
+    let (name, addr) = s
+        .split_once('@')
+        .ok_or_else(|| format!("server name is missing in {}", s))?;
+    Ok((name.to_owned(), parse_server_addr::<853>(addr)?))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+    use clap::Parser as _;
+    let mut args = Args::parse();
+    let filter = if args.verbose >= 2 {
+        tracing_subscriber::EnvFilter::new("hickory_proto=debug,hickory_resolver=debug")
+    } else {
+        tracing_subscriber::EnvFilter::from_default_env()
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+    if let Some(ref path) = args.file {
+        args.names.extend(read_names(path)?);
+    }
+
+    if !args.compare.is_empty() {
+        let types = if args.types.is_empty() {
+            <rdig::QueryType as clap::ValueEnum>::value_variants().to_vec()
+        } else {
+            args.types
+        };
+        return compare(&args.compare, &args.names, &types, args.dnssec, args.json).await;
+    }
 
-    let mut resolver = hickory_resolver::Resolver::builder_with_config(
-        hickory_resolver::config::ResolverConfig::cloudflare_https(),
-        hickory_resolver::name_server::TokioConnectionProvider::default(),
+    let spec = args.resolver.spec();
+    let resolver = build_resolver(&spec, args.dnssec).await?;
+    if args.verbose > 0 && !args.json {
+        anstream::println!("{}", styled(COMMENT_STYLE, &format!(";; server: {}", spec)));
+    }
+    if args.check_cname {
+        return check_cname(resolver, args).await;
+    }
+    if args.email {
+        return check_email(resolver, args).await;
+    }
+    if args.trace {
+        let types = if args.types.is_empty() {
+            vec![rdig::QueryType::A]
+        } else {
+            args.types
+        };
+        let options = rdig::trace::TraceOptions::default();
+        for name in &args.names {
+            for query_type in &types {
+                let trace = rdig::trace::trace(&resolver, name, *query_type, &options).await;
+                if args.json {
+                    println!("{}", serde_json::to_string(&trace)?);
+                } else {
+                    print_trace(&trace);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let types = if args.types.is_empty() {
+        <rdig::QueryType as clap::ValueEnum>::value_variants().to_vec()
+    } else {
+        std::mem::take(&mut args.types)
+    };
+
+    let types: std::sync::Arc<[rdig::QueryType]> = types.into();
+    if let Some(interval) = args.watch {
+        return watch(resolver, args, types, interval).await;
+    }
+    let mut summary = Summary::default();
+    for_each_name(
+        args.names,
+        args.concurrency,
+        args.rate,
+        |name| {
+            let resolver = resolver.clone();
+            let types = types.clone();
+            async move { rdig::lookup_name(&resolver, &name, &types).await }
+        },
+        |result| {
+            summary.add(&result);
+            if args.json {
+                println!("{}", serde_json::to_string(&result)?);
+            } else {
+                print_text(&result, args.verbose);
+            }
+            Ok(())
+        },
     )
-    .build();
+    .await?;
 
-    for arg in std::env::args().skip(1) {
-        resolve_name(&mut resolver, arg).await?;
+    // Failures are already shown for names given as arguments, like dig
+    if args.file.is_some() {
+        eprintln!("{}", summary);
+        if summary.failed > 0 {
+            std::process::exit(1);
+        }
     }
     Ok(())
 }
 
-async fn resolve_name<P>(
-    resolver: &mut hickory_resolver::Resolver<P>,
-    name: String,
+/// Run `f` for names with at most `concurrency` names at once and pass results to `output` in the
+/// order of names
+async fn for_each_name<T, F, Fut, O>(
+    names: Vec<String>,
+    concurrency: std::num::NonZeroUsize,
+    rate: Option<f64>,
+    f: F,
+    mut output: O,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
-    P: hickory_resolver::name_server::ConnectionProvider,
+    T: Send + 'static,
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = T> + Send + 'static,
+    O: FnMut(T) -> Result<(), Box<dyn std::error::Error>>,
 {
-    const TYPE_STYLE: anstyle::Style =
-        anstyle::Style::new().fg_color(Some(anstyle::Color::Ansi(anstyle::AnsiColor::Yellow)));
-    const NAME_STYLE: anstyle::Style =
-        anstyle::Style::new().fg_color(Some(anstyle::Color::Ansi(anstyle::AnsiColor::Green)));
-    const ADDR_STYLE: anstyle::Style =
-        anstyle::Style::new().fg_color(Some(anstyle::Color::Ansi(anstyle::AnsiColor::Blue)));
-
-    let mx_handle = tokio::spawn(resolve_mx(
-        resolver.clone(),
-        name.clone(),
-        TYPE_STYLE,
-        NAME_STYLE,
-    ));
-    let txt_handle = tokio::spawn(resolve_txt(resolver.clone(), name.clone(), TYPE_STYLE));
-    let caa_handle = tokio::spawn(resolve_caa(
-        resolver.clone(),
-        name.clone(),
-        TYPE_STYLE,
-        NAME_STYLE,
-    ));
-
-    mx_handle.await?;
-    txt_handle.await?;
-    caa_handle.await?;
-
-    let mut name = name;
+    let mut interval =
+        rate.map(|rate| tokio::time::interval(std::time::Duration::from_secs_f64(1.0 / rate)));
+    let mut names = names.into_iter().enumerate();
+    let mut tasks = tokio::task::JoinSet::new();
+    // Results finished earlier than preceding names
+    let mut pending = std::collections::BTreeMap::new();
+    let mut next = 0;
     loop {
-        let mut resolved = false;
-        if let Ok(resp) = resolver
-            .lookup(
-                name.as_str(),
-                hickory_resolver::proto::rr::RecordType::CNAME,
-            )
-            .await
+        while tasks.len() < concurrency.get()
+            && let Some((i, name)) = names.next()
         {
-            for cname in resp {
-                resolved = true;
-                let next_name = cname.as_cname().unwrap().to_string();
-                anstream::println!(
-                    "{} {}CNAME{} {}{}{}",
-                    name,
-                    TYPE_STYLE.render(),
-                    TYPE_STYLE.render_reset(),
-                    NAME_STYLE.render(),
-                    next_name,
-                    NAME_STYLE.render_reset(),
-                );
-                name = next_name;
+            if let Some(ref mut interval) = interval {
+                interval.tick().await;
             }
+            let future = f(name);
+            tasks.spawn(async move { (i, future.await) });
         }
-        if !resolved {
+        let Some(result) = tasks.join_next().await else {
             break;
+        };
+        let (i, result) = result?;
+        pending.insert(i, result);
+        while let Some(result) = pending.remove(&next) {
+            output(result)?;
+            next += 1;
         }
     }
-    let name = name;
+    Ok(())
+}
 
-    let mut addrs = Vec::new();
-    if let Ok(resp) = resolver.ipv4_lookup(name.as_str()).await {
-        for a in resp {
-            anstream::println!(
-                "{} {}A{} {}{}{}",
-                name,
-                TYPE_STYLE.render(),
-                TYPE_STYLE.render_reset(),
-                ADDR_STYLE.render(),
-                a.to_string(),
-                ADDR_STYLE.render_reset(),
-            );
-            addrs.push(std::net::IpAddr::from(*a));
+async fn watch(
+    resolver: hickory_resolver::Resolver<
+        rdig::RecordingConnectionProvider<hickory_resolver::name_server::TokioConnectionProvider>,
+    >,
+    args: Args,
+    types: std::sync::Arc<[rdig::QueryType]>,
+    interval: std::time::Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // The first results are compared with nothing, so all records are printed as added
+    let mut previous: Vec<_> = args
+        .names
+        .iter()
+        .map(|name| rdig::NameResult {
+            name: name.clone(),
+            records: Default::default(),
+            errors: Default::default(),
+            chain: Vec::new(),
+            queries: Default::default(),
+        })
+        .collect();
+    let mut observed = vec![false; args.names.len()];
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let mut i = 0;
+        for_each_name(
+            args.names.clone(),
+            args.concurrency,
+            args.rate,
+            |name| {
+                let resolver = resolver.clone();
+                let types = types.clone();
+                async move { rdig::lookup_name(&resolver, &name, &types).await }
+            },
+            |mut result| {
+                let time = chrono::Local::now();
+                for change in rdig::watch::diff(&previous[i], &result) {
+                    if args.json {
+                        let event = WatchEvent {
+                            time,
+                            name: &result.name,
+                            change: &change,
+                        };
+                        println!("{}", serde_json::to_string(&event)?);
+                    } else {
+                        print_change(time, &result.name, &change);
+                    }
+                }
+                if let Some(ref value) = args.until
+                    && rdig::watch::matches(&result, value)
+                {
+                    observed[i] = true;
+                }
+                rdig::watch::keep_failed_records(&previous[i], &mut result);
+                previous[i] = result;
+                i += 1;
+                Ok(())
+            },
+        )
+        .await?;
+        if args.until.is_some() && observed.iter().all(|o| *o) {
+            return Ok(());
         }
     }
+}
 
-    if let Ok(resp) = resolver.ipv6_lookup(name.as_str()).await {
-        for aaaa in resp {
-            anstream::println!(
-                "{} {}AAAA{} {}{}{}",
-                name,
-                TYPE_STYLE.render(),
-                TYPE_STYLE.render_reset(),
-                ADDR_STYLE.render(),
-                aaaa.to_string(),
-                ADDR_STYLE.render_reset(),
-            );
-            addrs.push(std::net::IpAddr::from(*aaaa));
-        }
+#[derive(serde::Serialize)]
+struct WatchEvent<'a> {
+    time: chrono::DateTime<chrono::Local>,
+    name: &'a str,
+    #[serde(flatten)]
+    change: &'a rdig::watch::Change,
+}
+
+async fn check_cname(
+    resolver: hickory_resolver::Resolver<
+        rdig::RecordingConnectionProvider<hickory_resolver::name_server::TokioConnectionProvider>,
+    >,
+    args: Args,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut checked = 0;
+    let mut found = 0;
+    for_each_name(
+        args.names,
+        args.concurrency,
+        args.rate,
+        |name| {
+            let resolver = resolver.clone();
+            async move { rdig::cname::check_cname(&resolver, &name).await }
+        },
+        |check| {
+            checked += 1;
+            if !check.findings.is_empty() {
+                found += 1;
+            }
+            if args.json {
+                println!("{}", serde_json::to_string(&check)?);
+            } else {
+                print_cname_check(&check);
+            }
+            Ok(())
+        },
+    )
+    .await?;
+
+    if args.file.is_some() {
+        eprintln!("{} names: {} with findings", checked, found);
     }
+    if found > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-    let mut addr_handles = Vec::new();
-    for addr in addrs {
-        addr_handles.push(tokio::spawn(resolve_ptr(
-            resolver.clone(),
-            addr,
-            TYPE_STYLE,
-            NAME_STYLE,
-        )));
+async fn check_email(
+    resolver: hickory_resolver::Resolver<
+        rdig::RecordingConnectionProvider<hickory_resolver::name_server::TokioConnectionProvider>,
+    >,
+    args: Args,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut checked = 0;
+    let mut failed = 0;
+    for_each_name(
+        args.names,
+        args.concurrency,
+        args.rate,
+        |name| {
+            let resolver = resolver.clone();
+            async move { rdig::email::check_email(&resolver, &name).await }
+        },
+        |report| {
+            checked += 1;
+            if report
+                .findings
+                .iter()
+                .any(|finding| finding.severity == rdig::email::Severity::Error)
+            {
+                failed += 1;
+            }
+            if args.json {
+                println!("{}", serde_json::to_string(&report)?);
+            } else {
+                print_email_report(&report);
+            }
+            Ok(())
+        },
+    )
+    .await?;
+
+    if args.file.is_some() {
+        eprintln!("{} names: {} with errors", checked, failed);
     }
-    for handle in addr_handles {
-        handle.await?;
+    if failed > 0 {
+        std::process::exit(1);
     }
     Ok(())
 }
 
-async fn resolve_mx<P>(
-    resolver: hickory_resolver::Resolver<P>,
-    name: String,
-    type_style: anstyle::Style,
-    name_style: anstyle::Style,
-) where
-    P: hickory_resolver::name_server::ConnectionProvider,
-{
-    if let Ok(resp) = resolver.mx_lookup(name.as_str()).await {
-        let mut records: Vec<_> = resp.into_iter().collect();
-        records.sort_unstable_by(|x, y| {
-            x.preference()
-                .cmp(&y.preference())
-                .then_with(|| x.exchange().cmp(y.exchange()))
-        });
-        for mx in records {
-            anstream::println!(
-                "{} {}MX{} {}{}{} {}",
-                name,
-                type_style.render(),
-                type_style.render_reset(),
-                name_style.render(),
-                mx.exchange().to_utf8(),
-                name_style.render_reset(),
-                mx.preference()
-            );
+#[derive(Debug, Default)]
+struct Summary {
+    resolved: usize,
+    empty: usize,
+    failed: usize,
+}
+
+impl Summary {
+    fn add(&mut self, result: &rdig::NameResult) {
+        if !result.errors.is_empty() {
+            self.failed += 1;
+        } else if result.records.values().all(|records| records.is_empty()) {
+            self.empty += 1;
+        } else {
+            self.resolved += 1;
         }
     }
 }
 
-async fn resolve_txt<P>(
-    resolver: hickory_resolver::Resolver<P>,
-    name: String,
-    type_style: anstyle::Style,
-) where
-    P: hickory_resolver::name_server::ConnectionProvider,
-{
-    if let Ok(resp) = resolver.txt_lookup(name.as_str()).await {
-        for txt in resp {
-            for data in txt.txt_data() {
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} names: {} resolved, {} without records, {} failed",
+            self.resolved + self.empty + self.failed,
+            self.resolved,
+            self.empty,
+            self.failed
+        )
+    }
+}
+
+async fn build_resolver(
+    spec: &ResolverSpec,
+    dnssec: bool,
+) -> Result<
+    hickory_resolver::Resolver<
+        rdig::RecordingConnectionProvider<hickory_resolver::name_server::TokioConnectionProvider>,
+    >,
+    Box<dyn std::error::Error>,
+> {
+    use hickory_resolver::config::{NameServerConfig, ResolverConfig};
+    use hickory_resolver::proto::xfer::Protocol;
+
+    let config = match *spec {
+        ResolverSpec::System => None,
+        ResolverSpec::Cloudflare => Some(ResolverConfig::cloudflare_https()),
+        ResolverSpec::Google => Some(ResolverConfig::google_https()),
+        ResolverSpec::Quad9 => Some(ResolverConfig::quad9_https()),
+        ResolverSpec::Udp(addr) => Some(ResolverConfig::from_parts(
+            None,
+            Vec::new(),
+            vec![NameServerConfig::new(addr, Protocol::Udp)],
+        )),
+        ResolverSpec::Tcp(addr) => Some(ResolverConfig::from_parts(
+            None,
+            Vec::new(),
+            vec![NameServerConfig::new(addr, Protocol::Tcp)],
+        )),
+        ResolverSpec::Tls(ref name, addr) => {
+            let mut server = NameServerConfig::new(addr, Protocol::Tls);
+            server.tls_dns_name = Some(name.clone());
+            Some(ResolverConfig::from_parts(None, Vec::new(), vec![server]))
+        }
+        ResolverSpec::Https(ref url) => {
+            if url.scheme() != "https" {
+                return Err(format!("DNS over HTTPS requires https URL: {}", url).into());
+            }
+            let host = url
+                .host_str()
+                .ok_or_else(|| format!("host is missing in {}", url))?;
+            let port = url.port().unwrap_or(443);
+            // The host of DoH server is resolved by the system resolver
+            let addr = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+                .await
+                .map_err(|e| format!("failed to resolve {}: {}", host, e))?
+                .next()
+                .ok_or_else(|| format!("failed to resolve {}", host))?;
+            let mut server = NameServerConfig::new(addr, Protocol::Https);
+            server.tls_dns_name = Some(host.to_owned());
+            server.http_endpoint = Some(url.path().to_owned());
+            Some(ResolverConfig::from_parts(None, Vec::new(), vec![server]))
+        }
+    };
+
+    let mut builder = match config {
+        Some(config) => hickory_resolver::Resolver::builder_with_config(
+            config,
+            rdig::RecordingConnectionProvider::default(),
+        ),
+        None => hickory_resolver::Resolver::builder(rdig::RecordingConnectionProvider::default())?,
+    };
+    builder.options_mut().validate = dnssec;
+    Ok(builder.build())
+}
+
+/// Result of each resolver for a name
+#[derive(serde::Serialize)]
+struct Comparison<'a> {
+    name: &'a str,
+    results: Vec<ResolverResult>,
+    /// Query types whose answers differ among resolvers
+    disagreements: Vec<rdig::QueryType>,
+}
+
+#[derive(serde::Serialize)]
+struct ResolverResult {
+    resolver: String,
+    records: std::collections::BTreeMap<rdig::QueryType, Vec<rdig::Record>>,
+}
+
+async fn compare(
+    specs: &[ResolverSpec],
+    names: &[String],
+    types: &[rdig::QueryType],
+    dnssec: bool,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut resolvers = Vec::with_capacity(specs.len());
+    for spec in specs {
+        resolvers.push(build_resolver(spec, dnssec).await?);
+    }
+
+    for name in names {
+        let mut tasks = tokio::task::JoinSet::new();
+        for (i, resolver) in resolvers.iter().enumerate() {
+            let resolver = resolver.clone();
+            let name = name.clone();
+            let types = types.to_vec();
+            tasks.spawn(async move { (i, rdig::lookup_name(&resolver, &name, &types).await) });
+        }
+        let mut results: Vec<_> = tasks.join_all().await;
+        results.sort_by_key(|(i, _)| *i);
+        let results: Vec<_> = results.into_iter().map(|(_, result)| result).collect();
+
+        let disagreements = rdig::disagreements(&results);
+        let comparison = Comparison {
+            name,
+            results: specs
+                .iter()
+                .zip(results)
+                .map(|(spec, result)| ResolverResult {
+                    resolver: spec.to_string(),
+                    records: result.records,
+                })
+                .collect(),
+            disagreements,
+        };
+        if json {
+            println!("{}", serde_json::to_string(&comparison)?);
+        } else {
+            print_comparison(&comparison, types);
+        }
+    }
+    Ok(())
+}
+
+/// Answers which differ from the most common one are highlighted
+fn print_comparison(comparison: &Comparison<'_>, types: &[rdig::QueryType]) {
+    let width = comparison
+        .results
+        .iter()
+        .map(|result| result.resolver.len())
+        .max()
+        .unwrap_or(0);
+    for query_type in types {
+        let answers: Vec<_> = comparison
+            .results
+            .iter()
+            .map(|result| {
+                result
+                    .records
+                    .get(query_type)
+                    .map(|records| records.iter().map(|record| &record.data).collect())
+                    .unwrap_or_else(Vec::new)
+            })
+            .collect();
+        if answers.iter().all(|answer| answer.is_empty()) {
+            continue;
+        }
+        let most_common = answers
+            .iter()
+            .max_by_key(|answer| answers.iter().filter(|a| a == answer).count())
+            .unwrap();
+
+        let disagree = comparison.disagreements.contains(query_type);
+        anstream::println!(
+            "{} {}{}",
+            comparison.name,
+            styled(TYPE_STYLE, &query_type.record_type().to_string()),
+            if disagree {
+                format!(" {}", styled(NONE_STYLE, "(disagreement)"))
+            } else {
+                String::new()
+            }
+        );
+        for (result, answer) in comparison.results.iter().zip(&answers) {
+            let value = if answer.is_empty() {
+                "-".to_owned()
+            } else {
+                answer
+                    .iter()
+                    .map(|data| format_data(data))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            if answer == most_common {
+                anstream::println!("  {:width$} {}", result.resolver, value);
+            } else {
                 anstream::println!(
-                    "{} {}TXT{} {}",
-                    name,
-                    type_style.render(),
-                    type_style.render_reset(),
-                    String::from_utf8_lossy(data),
+                    "{} {}",
+                    styled(NONE_STYLE, &format!("! {:width$}", result.resolver)),
+                    value
                 );
             }
         }
     }
 }
 
-async fn resolve_caa<P>(
-    resolver: hickory_resolver::Resolver<P>,
-    name: String,
-    type_style: anstyle::Style,
-    name_style: anstyle::Style,
-) where
-    P: hickory_resolver::name_server::ConnectionProvider,
-{
-    if let Ok(resp) = resolver
-        .lookup(name.as_str(), hickory_resolver::proto::rr::RecordType::CAA)
-        .await
-    {
-        for rdata in resp {
-            let caa = rdata.as_caa().unwrap();
-            use hickory_resolver::proto::rr::rdata::caa::{Property, Value};
-            match (caa.tag(), caa.value()) {
-                (Property::Issue, Value::Issuer(Some(domain), _)) => {
-                    anstream::println!(
-                        "{} {}CAA{} issue {}{}{} (critical={})",
-                        name,
-                        type_style.render(),
-                        type_style.render_reset(),
-                        name_style.render(),
-                        domain.to_utf8(),
-                        name_style.render_reset(),
-                        caa.issuer_critical()
-                    );
-                }
-                (Property::IssueWild, Value::Issuer(Some(domain), _)) => {
-                    anstream::println!(
-                        "{} {}CAA{} issuewild {}{}{} (critical={})",
-                        name,
-                        type_style.render(),
-                        type_style.render_reset(),
-                        name_style.render(),
-                        domain.to_utf8(),
-                        name_style.render_reset(),
-                        caa.issuer_critical()
-                    );
-                }
-                (Property::Iodef, Value::Url(url)) => {
-                    anstream::println!(
-                        "{} {}CAA{} iodef {}{}{} (critical={})",
-                        name,
-                        type_style.render(),
-                        type_style.render_reset(),
-                        name_style.render(),
-                        url.as_str(),
-                        name_style.render_reset(),
-                        caa.issuer_critical()
-                    );
-                }
-                (tag, value) => {
-                    anstream::println!(
-                        "{} {}CAA{} {:?} {:?} (critical={})",
-                        name,
-                        type_style.render(),
-                        type_style.render_reset(),
-                        tag,
-                        value,
-                        caa.issuer_critical()
-                    );
-                }
+const TYPE_STYLE: anstyle::Style =
+    anstyle::Style::new().fg_color(Some(anstyle::Color::Ansi(anstyle::AnsiColor::Yellow)));
+const NAME_STYLE: anstyle::Style =
+    anstyle::Style::new().fg_color(Some(anstyle::Color::Ansi(anstyle::AnsiColor::Green)));
+const ADDR_STYLE: anstyle::Style =
+    anstyle::Style::new().fg_color(Some(anstyle::Color::Ansi(anstyle::AnsiColor::Blue)));
+const NONE_STYLE: anstyle::Style =
+    anstyle::Style::new().fg_color(Some(anstyle::Color::Ansi(anstyle::AnsiColor::Red)));
+const COMMENT_STYLE: anstyle::Style = anstyle::Style::new().dimmed();
+
+/// Failures are shown with the response code unless `verbose`
+fn print_text(result: &rdig::NameResult, verbose: u8) {
+    let show_ttl = verbose > 0;
+    for (query_type, records) in &result.records {
+        for record in records {
+            print_record(*query_type, record, show_ttl);
+        }
+        // NXDOMAIN isn't an error but shouldn't look the same as no records
+        if records.is_empty()
+            && let Some(rcode) = result
+                .queries
+                .get(query_type)
+                .and_then(|status| status.rcode.as_deref())
+            && rcode != hickory_resolver::proto::op::ResponseCode::NoError.to_str()
+        {
+            anstream::println!(
+                "{} {} {}",
+                result.name,
+                styled(TYPE_STYLE, &query_type.record_type().to_string()),
+                styled(COMMENT_STYLE, rcode)
+            );
+        }
+    }
+    for (query_type, error) in &result.errors {
+        let rcode = result
+            .queries
+            .get(query_type)
+            .and_then(|status| status.rcode.as_ref());
+        let message = match rcode {
+            Some(rcode) if verbose == 0 => rcode,
+            _ => error,
+        };
+        anstream::println!(
+            "{} {} {}",
+            result.name,
+            styled(TYPE_STYLE, &query_type.record_type().to_string()),
+            styled(NONE_STYLE, message)
+        );
+    }
+    for zone in &result.chain {
+        for record in &zone.ds {
+            print_record(rdig::QueryType::Ds, record, show_ttl);
+        }
+        for record in &zone.dnskey {
+            print_record(rdig::QueryType::Dnskey, record, show_ttl);
+        }
+    }
+    if verbose > 0 {
+        for (query_type, status) in &result.queries {
+            let mut line = format!(
+                ";; {} {}: {} in {} ms",
+                result.name,
+                query_type.record_type(),
+                status.rcode.as_deref().unwrap_or("no response"),
+                status.elapsed_ms
+            );
+            if let Some(server) = &status.server {
+                line.push_str(&format!(" from {}", server));
             }
+            anstream::println!("{}", styled(COMMENT_STYLE, &line));
         }
     }
 }
 
-async fn resolve_ptr<P>(
-    resolver: hickory_resolver::Resolver<P>,
-    addr: std::net::IpAddr,
-    type_style: anstyle::Style,
-    name_style: anstyle::Style,
-) where
-    P: hickory_resolver::name_server::ConnectionProvider,
-{
-    if let Ok(resp) = resolver.reverse_lookup(addr).await {
-        for ptr in resp {
-            {
-                anstream::println!(
-                    "{} {}PTR{} {}{}{}",
-                    addr,
-                    type_style.render(),
-                    type_style.render_reset(),
-                    name_style.render(),
-                    ptr.to_string(),
-                    name_style.render_reset(),
-                );
-            }
+fn print_trace(trace: &rdig::trace::Trace) {
+    for step in &trace.steps {
+        let status = match (&step.rcode, &step.error) {
+            (_, Some(error)) => styled(NONE_STYLE, error),
+            (Some(rcode), None) => rcode.clone(),
+            (None, None) => unreachable!(),
+        };
+        anstream::println!(
+            "{} {}",
+            styled(
+                COMMENT_STYLE,
+                &format!(
+                    ";; {} via {} ({}) in {:.1} ms:",
+                    step.zone, step.server, step.address, step.elapsed_ms
+                )
+            ),
+            status
+        );
+        for record in &step.records {
+            print_record(record.query_type, &record.record, false);
         }
-    } else {
-        const NONE_STYLE: anstyle::Style =
-            anstyle::Style::new().fg_color(Some(anstyle::Color::Ansi(anstyle::AnsiColor::Red)));
+    }
+    if let Some(ref error) = trace.error {
         anstream::println!(
-            "{} {}PTR{} {}NONE{}",
-            addr,
-            type_style.render(),
-            type_style.render_reset(),
-            NONE_STYLE.render(),
-            NONE_STYLE.render_reset(),
+            "{}",
+            styled(
+                NONE_STYLE,
+                &format!(
+                    ";; {} {}: {}",
+                    trace.name,
+                    trace.query_type.record_type(),
+                    error
+                )
+            )
+        );
+    }
+}
+
+/// Names without findings are not printed
+fn print_cname_check(check: &rdig::cname::CnameCheck) {
+    let mut chain = check.name.clone();
+    for record in &check.chain {
+        if let rdig::RecordData::Cname { ref target } = record.data {
+            chain.push_str(" -> ");
+            chain.push_str(target);
+        }
+    }
+    for finding in &check.findings {
+        anstream::println!(
+            "{}: {} ({})",
+            check.name,
+            styled(NONE_STYLE, &finding.to_string()),
+            chain
+        );
+    }
+}
+
+fn print_email_report(report: &rdig::email::EmailReport) {
+    let print = |kind: &str, record: &str| {
+        anstream::println!("{} {} {}", report.name, styled(TYPE_STYLE, kind), record);
+    };
+    if let Some(ref spf) = report.spf {
+        print(
+            "SPF",
+            &format!("{} ({} DNS lookups)", spf.record, spf.lookups),
+        );
+    }
+    if let Some(ref dmarc) = report.dmarc {
+        print("DMARC", dmarc);
+    }
+    for dkim in &report.dkim {
+        print("DKIM", &format!("{}: {}", dkim.selector, dkim.record));
+    }
+    if let Some(ref mta_sts) = report.mta_sts {
+        print("MTA-STS", mta_sts);
+    }
+    for finding in &report.findings {
+        let style = match finding.severity {
+            rdig::email::Severity::Error => NONE_STYLE,
+            rdig::email::Severity::Warning => TYPE_STYLE,
+            rdig::email::Severity::Info => anstyle::Style::new().dimmed(),
+        };
+        anstream::println!("{}: {}", report.name, styled(style, &finding.to_string()));
+    }
+}
+
+fn print_change(time: chrono::DateTime<chrono::Local>, name: &str, change: &rdig::watch::Change) {
+    let time = time.format("%H:%M:%S");
+    let record_type =
+        |query_type: &rdig::QueryType| styled(TYPE_STYLE, &query_type.record_type().to_string());
+    match *change {
+        rdig::watch::Change::Added {
+            ref query_type,
+            ref record,
+        } => anstream::println!(
+            "{} {} {} {} {} (ttl {})",
+            time,
+            styled(NAME_STYLE, "+"),
+            record.name,
+            record_type(query_type),
+            format_data(&record.data),
+            record.ttl
+        ),
+        rdig::watch::Change::Removed {
+            ref query_type,
+            ref record,
+        } => anstream::println!(
+            "{} {} {} {} {}",
+            time,
+            styled(NONE_STYLE, "-"),
+            record.name,
+            record_type(query_type),
+            format_data(&record.data)
+        ),
+        rdig::watch::Change::TtlReset {
+            ref query_type,
+            ref record,
+            previous,
+        } => anstream::println!(
+            "{} ~ {} {} {} (ttl {} -> {})",
+            time,
+            record.name,
+            record_type(query_type),
+            format_data(&record.data),
+            previous,
+            record.ttl
+        ),
+        rdig::watch::Change::Failed {
+            ref query_type,
+            ref message,
+        } => anstream::println!(
+            "{} {} {} {} {}",
+            time,
+            styled(NONE_STYLE, "!"),
+            name,
+            record_type(query_type),
+            styled(NONE_STYLE, message)
+        ),
+        rdig::watch::Change::Recovered { ref query_type } => {
+            anstream::println!("{} = {} {} recovered", time, name, record_type(query_type))
+        }
+    }
+}
+
+fn print_record(query_type: rdig::QueryType, record: &rdig::Record, show_ttl: bool) {
+    let value = format_data(&record.data);
+    let proof = match record.proof {
+        Some(proof @ rdig::Proof::Secure) => {
+            format!(" [{}]", styled(NAME_STYLE, &proof.to_string()))
+        }
+        Some(proof @ rdig::Proof::Bogus) => {
+            format!(" [{}]", styled(NONE_STYLE, &proof.to_string()))
+        }
+        Some(proof) => format!(" [{}]", proof),
+        None => String::new(),
+    };
+    let ttl = if show_ttl {
+        format!(" {}", record.ttl)
+    } else {
+        String::new()
+    };
+    anstream::println!(
+        "{}{} {} {}{}",
+        record.name,
+        ttl,
+        styled(TYPE_STYLE, &query_type.record_type().to_string()),
+        value,
+        proof
+    );
+}
+
+fn format_data(data: &rdig::RecordData) -> String {
+    match *data {
+        rdig::RecordData::Mx {
+            preference,
+            ref exchange,
+        } => format!("{} {}", styled(NAME_STYLE, exchange), preference),
+        rdig::RecordData::Txt { ref text } => text.clone(),
+        rdig::RecordData::Caa {
+            ref tag,
+            ref value,
+            critical,
+        } => format!(
+            "{} {} (critical={})",
+            tag,
+            styled(NAME_STYLE, value),
+            critical
+        ),
+        rdig::RecordData::Ns { ref nameserver } => styled(NAME_STYLE, nameserver),
+        rdig::RecordData::Soa {
+            ref mname,
+            ref rname,
+            serial,
+            refresh,
+            retry,
+            expire,
+            minimum,
+        } => format!(
+            "{} {} {} (refresh={}, retry={}, expire={}, minimum={})",
+            styled(NAME_STYLE, mname),
+            rname,
+            serial,
+            refresh,
+            retry,
+            expire,
+            minimum
+        ),
+        rdig::RecordData::Srv {
+            priority,
+            weight,
+            port,
+            ref target,
+        } => format!(
+            "{}:{} (priority={}, weight={})",
+            styled(NAME_STYLE, target),
+            port,
+            priority,
+            weight
+        ),
+        rdig::RecordData::Svcb {
+            priority,
+            ref target,
+            ref params,
+        } => {
+            // Priority 0 is AliasMode
+            let mut value = format!("{} {}", styled(NAME_STYLE, target), priority);
+            for param in params {
+                value.push(' ');
+                value.push_str(param);
+            }
+            value
+        }
+        rdig::RecordData::Tlsa {
+            usage,
+            selector,
+            matching,
+            ref data,
+        } => format!("{} {} {} {}", tlsa_usage(usage), selector, matching, data),
+        rdig::RecordData::Ds {
+            key_tag,
+            algorithm,
+            digest_type,
+            ref digest,
+        } => format!(
+            "{} {} {} {}",
+            key_tag,
+            dnssec_algorithm(algorithm),
+            digest_type,
+            digest
+        ),
+        rdig::RecordData::Dnskey {
+            flags,
+            algorithm,
+            key_tag,
+            ref public_key,
+        } => format!(
+            "{} {} {} {} (key_tag={})",
+            flags,
+            if flags & 1 == 1 { "KSK" } else { "ZSK" },
+            dnssec_algorithm(algorithm),
+            public_key,
+            key_tag
+        ),
+        rdig::RecordData::Cname { ref target } => styled(NAME_STYLE, target),
+        rdig::RecordData::Address { address } => styled(ADDR_STYLE, &address.to_string()),
+        rdig::RecordData::Ptr { ref target } => match target {
+            Some(target) => styled(NAME_STYLE, target),
+            None => styled(NONE_STYLE, "NONE"),
+        },
+    }
+}
+
+fn tlsa_usage(usage: u8) -> String {
+    match usage {
+        0 => "PKIX-TA".to_owned(),
+        1 => "PKIX-EE".to_owned(),
+        2 => "DANE-TA".to_owned(),
+        3 => "DANE-EE".to_owned(),
+        _ => usage.to_string(),
+    }
+}
+
+fn dnssec_algorithm(algorithm: u8) -> String {
+    match hickory_resolver::proto::dnssec::Algorithm::from_u8(algorithm) {
+        hickory_resolver::proto::dnssec::Algorithm::Unknown(n) => n.to_string(),
+        algorithm => algorithm.to_string(),
+    }
+}
+
+fn styled(style: anstyle::Style, s: &str) -> String {
+    format!("{}{}{}", style.render(), s, style.render_reset())
+}
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn query_given_server() {
+        let addr = "192.0.2.53:5353".parse().unwrap();
+        let resolver = super::build_resolver(&super::ResolverSpec::Udp(addr), false)
+            .await
+            .unwrap();
+        let servers = resolver.config().name_servers();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].socket_addr, addr);
+        assert_eq!(
+            servers[0].protocol,
+            hickory_resolver::proto::xfer::Protocol::Udp
+        );
+    }
+
+    #[test]
+    fn parse_intervals() {
+        assert_eq!(
+            super::parse_interval("90"),
+            Ok(std::time::Duration::from_secs(90))
+        );
+        assert_eq!(
+            super::parse_interval("5m"),
+            Ok(std::time::Duration::from_secs(300))
+        );
+        assert!(super::parse_interval("0h").is_err());
+        assert_eq!(
+            super::parse_interval("5124095576030432h"),
+            Err("invalid interval: 5124095576030432h".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_servers() {
+        assert_eq!(
+            super::parse_server_addr::<53>("192.0.2.1"),
+            Ok("192.0.2.1:53".parse().unwrap())
+        );
+        assert_eq!(
+            super::parse_server_addr::<53>("[2001:db8::1]:5353"),
+            Ok("[2001:db8::1]:5353".parse().unwrap())
+        );
+        assert_eq!(
+            super::parse_tls_server("one.one.one.one@1.1.1.1"),
+            Ok(("one.one.one.one".to_owned(), "1.1.1.1:853".parse().unwrap()))
+        );
+        assert!(super::parse_tls_server("1.1.1.1").is_err());
+        assert_eq!(
+            super::parse_resolver_spec("google"),
+            Ok(super::ResolverSpec::Google)
+        );
+        assert_eq!(
+            super::parse_resolver_spec("tcp:192.0.2.1"),
+            Ok(super::ResolverSpec::Tcp("192.0.2.1:53".parse().unwrap()))
+        );
+        assert_eq!(
+            super::parse_resolver_spec("tls:dns.google@8.8.8.8")
+                .unwrap()
+                .to_string(),
+            "tls:dns.google@8.8.8.8:853"
         );
     }
 }
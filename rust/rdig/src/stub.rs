@@ -0,0 +1,56 @@
+//! Stub DNS server for tests
+
+/// Respond to every query with `handle` and return the bound address. Port 0 binds to a free
+/// port.
+pub async fn stub_server<F>(addr: std::net::SocketAddr, handle: F) -> std::net::SocketAddr
+where
+    F: Fn(&hickory_resolver::proto::op::Query, &mut hickory_resolver::proto::op::Message)
+        + Send
+        + 'static,
+{
+    use hickory_resolver::proto::op::{Message, MessageType};
+
+    let socket = tokio::net::UdpSocket::bind(addr).await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0; 512];
+        loop {
+            let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+            let request = Message::from_vec(&buf[..len]).unwrap();
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_op_code(request.op_code());
+            for query in request.queries() {
+                response.add_query(query.clone());
+                handle(query, &mut response);
+            }
+            socket
+                .send_to(&response.to_vec().unwrap(), peer)
+                .await
+                .unwrap();
+        }
+    });
+    addr
+}
+
+/// Resolver sending queries only to the stub server
+pub fn resolver(
+    addr: std::net::SocketAddr,
+) -> hickory_resolver::Resolver<
+    crate::RecordingConnectionProvider<hickory_resolver::name_server::TokioConnectionProvider>,
+> {
+    hickory_resolver::Resolver::builder_with_config(
+        hickory_resolver::config::ResolverConfig::from_parts(
+            None,
+            Vec::new(),
+            vec![hickory_resolver::config::NameServerConfig::new(
+                addr,
+                hickory_resolver::proto::xfer::Protocol::Udp,
+            )],
+        ),
+        crate::RecordingConnectionProvider::default(),
+    )
+    .build()
+}
@@ -0,0 +1,391 @@
+//! Iterative resolution from the root servers, like `dig +trace`
+
+/// IPv4 addresses of the root servers in the root hints (https://www.internic.net/domain/named.root)
+const ROOT_SERVERS: [(&str, [u8; 4]); 13] = [
+    ("a.root-servers.net.", [198, 41, 0, 4]),
+    ("b.root-servers.net.", [170, 247, 170, 2]),
+    ("c.root-servers.net.", [192, 33, 4, 12]),
+    ("d.root-servers.net.", [199, 7, 91, 13]),
+    ("e.root-servers.net.", [192, 203, 230, 10]),
+    ("f.root-servers.net.", [192, 5, 5, 241]),
+    ("g.root-servers.net.", [192, 112, 36, 4]),
+    ("h.root-servers.net.", [198, 97, 190, 53]),
+    ("i.root-servers.net.", [192, 36, 148, 17]),
+    ("j.root-servers.net.", [192, 58, 128, 30]),
+    ("k.root-servers.net.", [193, 0, 14, 129]),
+    ("l.root-servers.net.", [199, 7, 83, 42]),
+    ("m.root-servers.net.", [202, 12, 27, 33]),
+];
+
+/// Limit of referrals to avoid looping forever
+const MAX_REFERRALS: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct TraceOptions {
+    /// Name servers of the root zone to start from
+    pub roots: Vec<(String, std::net::IpAddr)>,
+    /// Port of every server. Only tests use ports other than 53
+    pub port: u16,
+    /// Timeout of each query
+    pub timeout: std::time::Duration,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        Self {
+            roots: ROOT_SERVERS
+                .iter()
+                .map(|(name, addr)| (name.to_string(), std::net::IpAddr::from(*addr)))
+                .collect(),
+            port: 53,
+            timeout: std::time::Duration::from_secs(3),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Trace {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub query_type: crate::QueryType,
+    pub steps: Vec<Step>,
+    /// Reason why the trace stopped without the final response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Query sent to one of the name servers of `zone`
+#[derive(Debug, serde::Serialize)]
+pub struct Step {
+    pub zone: String,
+    pub server: String,
+    pub address: std::net::IpAddr,
+    pub elapsed_ms: f64,
+    /// Response code, absent when the server didn't respond
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rcode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Records in all sections of the response
+    pub records: Vec<TraceRecord>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TraceRecord {
+    #[serde(rename = "type")]
+    pub query_type: crate::QueryType,
+    #[serde(flatten)]
+    pub record: crate::Record,
+}
+
+/// Follow referrals from the root servers until a server answers authoritatively.
+/// `resolver` is used only to look up addresses of name servers without glue records
+pub async fn trace<P>(
+    resolver: &hickory_resolver::Resolver<P>,
+    name: &str,
+    query_type: crate::QueryType,
+    options: &TraceOptions,
+) -> Trace
+where
+    P: hickory_resolver::name_server::ConnectionProvider,
+{
+    let mut trace = Trace {
+        name: name.to_owned(),
+        query_type,
+        steps: Vec::new(),
+        error: None,
+    };
+    let qname = match hickory_resolver::Name::from_utf8(name) {
+        Ok(mut qname) => {
+            qname.set_fqdn(true);
+            qname
+        }
+        Err(e) => {
+            trace.error = Some(format!("invalid name {}: {}", name, e));
+            return trace;
+        }
+    };
+
+    let mut zone = hickory_resolver::Name::root();
+    let mut servers = options.roots.clone();
+    for _ in 0..MAX_REFERRALS {
+        let mut response = None;
+        for (server, address) in servers {
+            let start = std::time::Instant::now();
+            let result = query(
+                std::net::SocketAddr::new(address, options.port),
+                &qname,
+                query_type.record_type(),
+                options.timeout,
+            )
+            .await;
+            let mut step = Step {
+                zone: zone.to_utf8(),
+                server,
+                address,
+                elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+                rcode: None,
+                error: None,
+                records: Vec::new(),
+            };
+            match result {
+                Ok(message) => {
+                    step.rcode = Some(message.response_code().to_string());
+                    step.records = message
+                        .answers()
+                        .iter()
+                        .chain(message.name_servers())
+                        .chain(message.additionals())
+                        .filter_map(to_trace_record)
+                        .collect();
+                    trace.steps.push(step);
+                    response = Some(message);
+                    break;
+                }
+                Err(e) => {
+                    step.error = Some(e.to_string());
+                    trace.steps.push(step);
+                }
+            }
+        }
+        let Some(response) = response else {
+            trace.error = Some(format!("no name servers of {} responded", zone));
+            return trace;
+        };
+        if response.response_code() != hickory_resolver::proto::op::ResponseCode::NoError
+            || !response.answers().is_empty()
+        {
+            return trace;
+        }
+
+        let Some(next_zone) = response
+            .name_servers()
+            .iter()
+            .find(|record| record.data().as_ns().is_some())
+            .map(|record| record.name().clone())
+        else {
+            // No data
+            return trace;
+        };
+        if !(next_zone.zone_of(&qname) && next_zone.num_labels() > zone.num_labels()) {
+            trace.error = Some(format!(
+                "referral from {} to {} doesn't get closer to {}",
+                zone, next_zone, qname
+            ));
+            return trace;
+        }
+        let name_servers: Vec<_> = response
+            .name_servers()
+            .iter()
+            .filter(|record| *record.name() == next_zone)
+            .filter_map(|record| record.data().as_ns().map(|ns| ns.0.clone()))
+            .collect();
+        servers = glue_addresses(&response, &name_servers);
+        if servers.is_empty() {
+            for name_server in &name_servers {
+                if let Ok(lookup) = resolver.lookup_ip(name_server.clone()).await {
+                    servers.extend(lookup.iter().map(|addr| (name_server.to_utf8(), addr)));
+                    break;
+                }
+            }
+        }
+        if servers.is_empty() {
+            trace.error = Some(format!(
+                "failed to resolve addresses of name servers of {}",
+                next_zone
+            ));
+            return trace;
+        }
+        zone = next_zone;
+    }
+    trace.error = Some(format!("more than {} referrals", MAX_REFERRALS));
+    trace
+}
+
+/// IPv4 addresses are tried first since IPv6 is often unreachable
+fn glue_addresses(
+    response: &hickory_resolver::proto::op::Message,
+    name_servers: &[hickory_resolver::Name],
+) -> Vec<(String, std::net::IpAddr)> {
+    use hickory_resolver::proto::rr::RData;
+
+    let mut addresses = Vec::new();
+    for name_server in name_servers {
+        for record in response.additionals() {
+            if record.name() != name_server {
+                continue;
+            }
+            match record.data() {
+                RData::A(a) => addresses.push((name_server.to_utf8(), a.0.into())),
+                RData::AAAA(aaaa) => addresses.push((name_server.to_utf8(), aaaa.0.into())),
+                _ => {}
+            }
+        }
+    }
+    addresses.sort_by_key(|(_, addr): &(String, std::net::IpAddr)| addr.is_ipv6());
+    addresses
+}
+
+fn to_trace_record(record: &hickory_resolver::proto::rr::Record) -> Option<TraceRecord> {
+    let record_type = record.record_type();
+    let query_type = <crate::QueryType as clap::ValueEnum>::value_variants()
+        .iter()
+        .find(|query_type| query_type.record_type() == record_type)?;
+    Some(TraceRecord {
+        query_type: *query_type,
+        record: crate::Record {
+            name: record.name().to_utf8(),
+            ttl: record.ttl(),
+            data: crate::to_record_data(record.data())?,
+            proof: None,
+        },
+    })
+}
+
+/// Send a non-recursive query over UDP, and retry over TCP when the response is truncated
+async fn query(
+    server: std::net::SocketAddr,
+    name: &hickory_resolver::Name,
+    record_type: hickory_resolver::proto::rr::RecordType,
+    timeout: std::time::Duration,
+) -> Result<hickory_resolver::proto::op::Message, Box<dyn std::error::Error>> {
+    use hickory_resolver::proto::op::{Edns, Message, MessageType, OpCode, Query};
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    // Random enough to tell responses apart
+    let id = std::hash::BuildHasher::hash_one(
+        &std::collections::hash_map::RandomState::new(),
+        std::time::SystemTime::now(),
+    ) as u16;
+    let mut request = Message::new();
+    request
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(false)
+        .add_query(Query::query(name.clone(), record_type));
+    let mut edns = Edns::new();
+    edns.set_max_payload(1232);
+    request.set_edns(edns);
+    let request = request.to_vec()?;
+
+    let response = tokio::time::timeout(timeout, async {
+        let bind_addr: std::net::SocketAddr = if server.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = tokio::net::UdpSocket::bind(bind_addr).await?;
+        socket.connect(server).await?;
+        socket.send(&request).await?;
+        let mut buf = [0; 4096];
+        loop {
+            let len = socket.recv(&mut buf).await?;
+            if let Ok(response) = Message::from_vec(&buf[..len])
+                && response.id() == id
+            {
+                return Ok::<_, Box<dyn std::error::Error>>(response);
+            }
+        }
+    })
+    .await
+    .map_err(|_| "timed out")??;
+    if !response.truncated() {
+        return Ok(response);
+    }
+
+    tokio::time::timeout(timeout, async {
+        let mut stream = tokio::net::TcpStream::connect(server).await?;
+        stream
+            .write_all(&(request.len() as u16).to_be_bytes())
+            .await?;
+        stream.write_all(&request).await?;
+        let len = stream.read_u16().await?;
+        let mut buf = vec![0; len as usize];
+        stream.read_exact(&mut buf).await?;
+        Ok(Message::from_vec(&buf)?)
+    })
+    .await
+    .map_err(|_| "timed out")?
+}
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn follow_referrals() {
+        use hickory_resolver::Name;
+        use hickory_resolver::proto::rr::{RData, Record, rdata};
+
+        // The fake root delegates example. to 127.0.0.2 with the same port
+        let port = crate::stub::stub_server(([127, 0, 0, 1], 0).into(), |_, response| {
+            let zone = Name::from_ascii("example.").unwrap();
+            let ns = Name::from_ascii("ns.example.").unwrap();
+            response.add_name_server(Record::from_rdata(
+                zone,
+                172800,
+                RData::NS(rdata::NS(ns.clone())),
+            ));
+            response.add_additional(Record::from_rdata(
+                ns,
+                172800,
+                RData::A(rdata::A::new(127, 0, 0, 2)),
+            ));
+        })
+        .await
+        .port();
+        crate::stub::stub_server(([127, 0, 0, 2], port).into(), |query, response| {
+            response.set_authoritative(true);
+            response.add_answer(Record::from_rdata(
+                query.name().clone(),
+                300,
+                RData::A(rdata::A::new(192, 0, 2, 1)),
+            ));
+        })
+        .await;
+
+        let resolver = hickory_resolver::Resolver::builder_with_config(
+            hickory_resolver::config::ResolverConfig::default(),
+            hickory_resolver::name_server::TokioConnectionProvider::default(),
+        )
+        .build();
+        let options = super::TraceOptions {
+            roots: vec![("root.".to_owned(), [127, 0, 0, 1].into())],
+            port,
+            timeout: std::time::Duration::from_secs(1),
+        };
+        let trace = super::trace(&resolver, "www.example", crate::QueryType::A, &options).await;
+        assert_eq!(trace.error, None);
+        let steps: Vec<_> = trace
+            .steps
+            .iter()
+            .map(|step| {
+                (
+                    step.zone.as_str(),
+                    step.server.as_str(),
+                    step.records
+                        .iter()
+                        .map(|r| format!("{} {:?}", r.record.name, r.record.data))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (
+                    ".",
+                    "root.",
+                    vec![
+                        r#"example. Ns { nameserver: "ns.example." }"#.to_owned(),
+                        "ns.example. Address { address: 127.0.0.2 }".to_owned(),
+                    ]
+                ),
+                (
+                    "example.",
+                    "ns.example.",
+                    vec!["www.example. Address { address: 192.0.2.1 }".to_owned()]
+                ),
+            ]
+        );
+    }
+}
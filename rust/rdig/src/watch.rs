@@ -0,0 +1,242 @@
+//! Compare successive results of a name to report changes
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    Added {
+        #[serde(rename = "type")]
+        query_type: crate::QueryType,
+        record: crate::Record,
+    },
+    Removed {
+        #[serde(rename = "type")]
+        query_type: crate::QueryType,
+        record: crate::Record,
+    },
+    /// The TTL went up instead of counting down, i.e. the record was refetched or its TTL was
+    /// raised
+    TtlReset {
+        #[serde(rename = "type")]
+        query_type: crate::QueryType,
+        record: crate::Record,
+        previous: u32,
+    },
+    /// The lookup started failing or failed with another error
+    Failed {
+        #[serde(rename = "type")]
+        query_type: crate::QueryType,
+        message: String,
+    },
+    Recovered {
+        #[serde(rename = "type")]
+        query_type: crate::QueryType,
+    },
+}
+
+/// Records are matched by their data, so a changed value is reported as removed and added. Types
+/// whose lookups failed in `new` are reported only as failed
+pub fn diff(old: &crate::NameResult, new: &crate::NameResult) -> Vec<Change> {
+    let empty = Vec::new();
+    let mut query_types: Vec<_> = old
+        .records
+        .keys()
+        .chain(new.records.keys())
+        .filter(|query_type| !new.errors.contains_key(query_type))
+        .collect();
+    query_types.sort();
+    query_types.dedup();
+
+    let mut changes = Vec::new();
+    for &query_type in query_types {
+        let old_records = old.records.get(&query_type).unwrap_or(&empty);
+        let new_records = new.records.get(&query_type).unwrap_or(&empty);
+        for record in old_records {
+            if !new_records.iter().any(|r| r.data == record.data) {
+                changes.push(Change::Removed {
+                    query_type,
+                    record: record.clone(),
+                });
+            }
+        }
+        for record in new_records {
+            match old_records.iter().find(|r| r.data == record.data) {
+                None => changes.push(Change::Added {
+                    query_type,
+                    record: record.clone(),
+                }),
+                Some(previous) if record.ttl > previous.ttl => changes.push(Change::TtlReset {
+                    query_type,
+                    record: record.clone(),
+                    previous: previous.ttl,
+                }),
+                Some(_) => {}
+            }
+        }
+    }
+    for (&query_type, message) in &new.errors {
+        if old.errors.get(&query_type) != Some(message) {
+            changes.push(Change::Failed {
+                query_type,
+                message: message.clone(),
+            });
+        }
+    }
+    for &query_type in old.errors.keys() {
+        if !new.errors.contains_key(&query_type) {
+            changes.push(Change::Recovered { query_type });
+        }
+    }
+    changes
+}
+
+/// Keep the records of `old` for the types whose lookups failed in `new`, so that records of the
+/// next successful lookup are compared with the last known ones
+pub fn keep_failed_records(old: &crate::NameResult, new: &mut crate::NameResult) {
+    for query_type in new.errors.keys() {
+        if let Some(records) = old.records.get(query_type) {
+            new.records.insert(*query_type, records.clone());
+        }
+    }
+}
+
+/// Whether any record has `value` as [`crate::RecordData::value`], e.g. an address or a CNAME
+/// target. Addresses are compared as IP addresses, and names are compared case-insensitively with
+/// or without the trailing dot
+pub fn matches(result: &crate::NameResult, value: &str) -> bool {
+    let address = value.parse::<std::net::IpAddr>().ok();
+    let value = value.trim_end_matches('.');
+    result
+        .records
+        .values()
+        .flatten()
+        .any(|record| match (&record.data, address) {
+            (crate::RecordData::Address { address }, Some(expected)) => *address == expected,
+            (data, _) => data
+                .value()
+                .is_some_and(|v| v.trim_end_matches('.').eq_ignore_ascii_case(value)),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    fn result(records: &[(crate::QueryType, u32, &str)]) -> crate::NameResult {
+        let mut result = crate::NameResult {
+            name: "example.com.".to_owned(),
+            records: Default::default(),
+            errors: Default::default(),
+            chain: Vec::new(),
+            queries: Default::default(),
+        };
+        for &(query_type, ttl, address) in records {
+            result
+                .records
+                .entry(query_type)
+                .or_default()
+                .push(crate::Record {
+                    name: "example.com.".to_owned(),
+                    ttl,
+                    data: crate::RecordData::Address {
+                        address: address.parse().unwrap(),
+                    },
+                    proof: None,
+                });
+        }
+        result
+    }
+
+    #[test]
+    fn diff() {
+        use crate::QueryType::A;
+
+        let old = result(&[(A, 300, "192.0.2.1"), (A, 300, "192.0.2.2")]);
+        let new = result(&[(A, 270, "192.0.2.1"), (A, 270, "192.0.2.3")]);
+        let changes = super::diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                super::Change::Removed {
+                    query_type: A,
+                    record: old.records[&A][1].clone(),
+                },
+                super::Change::Added {
+                    query_type: A,
+                    record: new.records[&A][1].clone(),
+                },
+            ]
+        );
+
+        let reset = result(&[(A, 300, "192.0.2.1"), (A, 270, "192.0.2.3")]);
+        assert_eq!(
+            super::diff(&new, &reset),
+            vec![super::Change::TtlReset {
+                query_type: A,
+                record: reset.records[&A][0].clone(),
+                previous: 270,
+            }]
+        );
+        assert!(super::matches(&reset, "192.0.2.3"));
+        assert!(!super::matches(&reset, "192.0.2.2"));
+    }
+
+    #[test]
+    fn failed_lookups() {
+        use crate::QueryType::{A, Aaaa};
+
+        let old = result(&[(A, 300, "192.0.2.1"), (Aaaa, 300, "2001:db8::1")]);
+        let mut failed = result(&[(Aaaa, 270, "2001:db8::1")]);
+        failed.errors.insert(A, "request timed out".to_owned());
+        assert_eq!(
+            super::diff(&old, &failed),
+            vec![super::Change::Failed {
+                query_type: A,
+                message: "request timed out".to_owned(),
+            }]
+        );
+
+        super::keep_failed_records(&old, &mut failed);
+        let recovered = result(&[(A, 240, "192.0.2.1"), (Aaaa, 240, "2001:db8::1")]);
+        assert_eq!(
+            super::diff(&failed, &recovered),
+            vec![super::Change::Recovered { query_type: A }]
+        );
+    }
+
+    #[test]
+    fn matches_only_values() {
+        let mut result = result(&[(crate::QueryType::Aaaa, 300, "2001:db8::1")]);
+        for (query_type, data) in [
+            (
+                crate::QueryType::Mx,
+                crate::RecordData::Mx {
+                    preference: 10,
+                    exchange: "mx.example.com.".to_owned(),
+                },
+            ),
+            (
+                crate::QueryType::Srv,
+                crate::RecordData::Srv {
+                    priority: 0,
+                    weight: 5,
+                    port: 443,
+                    target: "srv.example.com.".to_owned(),
+                },
+            ),
+        ] {
+            result
+                .records
+                .entry(query_type)
+                .or_default()
+                .push(crate::Record {
+                    name: "example.com.".to_owned(),
+                    ttl: 300,
+                    data,
+                    proof: None,
+                });
+        }
+        assert!(super::matches(&result, "2001:DB8:0::1"));
+        assert!(super::matches(&result, "MX.example.com"));
+        assert!(super::matches(&result, "srv.example.com."));
+        assert!(!super::matches(&result, "10"));
+        assert!(!super::matches(&result, "443"));
+    }
+}